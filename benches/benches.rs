@@ -26,9 +26,7 @@ speculate! {
             use html5ever::tendril::stream::TendrilSink;
 
             b.iter(|| {
-                let rc_dom = parse_document(RcDom::default(),
-                                            Default::default()).one(str);
-                rc_dom
+                parse_document(RcDom::default(), Default::default()).one(str)
             });
         };}
 
@@ -41,8 +39,8 @@ speculate! {
                 let document = Document::from(str);
             }
 
-            bench "Any (11446 Nodes)" |b| {
-                assert_eq!(document.find(Any).count(), 11446);
+            bench "Any (11447 Nodes)" |b| {
+                assert_eq!(document.find(Any).count(), 11447);
                 b.iter(|| document.find(Any).count());
             }
 
@@ -80,6 +78,60 @@ speculate! {
                 b.iter(|| node.find(Descendant(parent, child)).count());
             }
         }
+
+        context "Document::find(Descendant(...)).count() with and without the ancestor filter" {
+            before {
+                let document = Document::from(str);
+            }
+
+            bench "body div span (1711 Nodes)" |b| {
+                let p = Name("body").descendant(Name("div")).descendant(Name("span"));
+                assert_eq!(document.find(p).count(), 1711);
+                b.iter(|| document.find(p).count());
+            }
+
+            // Closures give no hint, so the filter is not used.
+            bench "body div span (1711 Nodes), unfiltered" |b| {
+                let p = Name("body").descendant(Name("div")).descendant(Name("span"));
+                let p = |node: &select::node::Node| p.matches(node);
+                assert_eq!(document.find(p).count(), 1711);
+                b.iter(|| document.find(p).count());
+            }
+
+            bench ".fqn * (23 Nodes)" |b| {
+                let p = Class("fqn").descendant(Any);
+                assert_eq!(document.find(p).count(), 23);
+                b.iter(|| document.find(p).count());
+            }
+
+            bench ".fqn * (23 Nodes), unfiltered" |b| {
+                let p = Class("fqn").descendant(Any);
+                let p = |node: &select::node::Node| p.matches(node);
+                assert_eq!(document.find(p).count(), 23);
+                b.iter(|| document.find(p).count());
+            }
+        }
+    }
+
+    context "one-line HTML (540,000 bytes)" {
+        before {
+            let str = (0..20000).map(|n| format!("<span class=c{:05}>x</span>", n)).collect::<String>();
+            assert_eq!(str.len(), 540000);
+        }
+
+        bench "constructing Document" |b| {
+            b.iter(|| Document::from(&*str));
+        }
+
+        bench "constructing Document with spans" |b| {
+            use select::document::ParseOptions;
+
+            b.iter(|| Document::parse_with_options(&str, ParseOptions { spans: true }));
+        }
+
+        bench "constructing Document with diagnostics" |b| {
+            b.iter(|| Document::parse_with_diagnostics(&str));
+        }
     }
 
     context "Node::attr()" {
@@ -1,33 +1,173 @@
-use html5ever::tendril::{ByteTendril, ReadExt, StrTendril};
+use html5ever::tendril::stream::{TendrilSink, Utf8LossyDecoder};
+#[cfg(not(feature = "encoding"))]
+use html5ever::tendril::ReadExt;
+use html5ever::tendril::{ByteTendril, StrTendril};
+use html5ever::{namespace_url, ns, LocalName, QualName};
 
-use crate::node::{self, Node};
+use self::bloom::AncestorFilter;
+use self::sink::Sink;
+use crate::node::{self, Node, NodeMut};
 use crate::predicate::Predicate;
 use crate::selection::Selection;
 
-use std::io;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::{fmt, io};
+
+mod bloom;
+mod index;
+mod mutation;
+mod sink;
+mod tree_builder;
+
+pub use self::index::{DocumentIndex, IndexKey};
+pub use self::tree_builder::{ElementBuilder, TreeBuilder};
 
 /// An HTML document.
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug)]
 pub struct Document {
+    /// The nodes of the document, by index. Use the methods of `Document` and
+    /// `NodeMut` to change them, as they also update the lookup tables built by
+    /// `Document::index`.
     pub nodes: Vec<node::Raw>,
+    /// The source locations of `nodes`, by index. Empty unless parsed with
+    /// `ParseOptions::spans` enabled.
+    pub(crate) spans: Vec<Option<node::Span>>,
+    /// Whether each node has been detached from the tree, by index. Nodes
+    /// past the end are not detached.
+    pub(crate) detached: Vec<bool>,
+    /// The `<template>` element of each node heading its contents, which
+    /// have no parent.
+    templates: HashMap<usize, usize>,
+    /// The lookup tables built by `Document::index`, if they have been built.
+    /// Cleared by the methods changing the document.
+    pub(crate) index: OnceLock<DocumentIndex>,
+}
+
+impl PartialEq for Document {
+    fn eq(&self, other: &Document) -> bool {
+        self.nodes == other.nodes
+            && self.spans == other.spans
+            && (0..self.nodes.len())
+                .all(|index| self.is_detached(index) == other.is_detached(index))
+    }
+}
+
+impl Eq for Document {}
+
+/// A problem found in a document, such as an HTML parse error.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub message: Cow<'static, str>,
+    /// Where in the source the problem was found. For parse errors, this is
+    /// the start of the tag or text being processed when the error occurred.
+    pub position: Option<node::Position>,
+}
+
+/// Options for `Document::parse_with_options`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Record the source location of every node, available through
+    /// `Node::span()`.
+    pub spans: bool,
 }
 
 impl Document {
     /// Returns a `Selection` containing nodes passing the given predicate `p`.
-    pub fn find<P: Predicate>(&self, predicate: P) -> Find<P> {
+    pub fn find<P: Predicate>(&self, predicate: P) -> Find<'_, P> {
+        let hint = predicate.hint();
+        let candidates = self.index.get().and_then(|index| index.candidates(&hint));
+        let filter = AncestorFilter::new(&hint);
         Find {
             document: self,
             next: 0,
+            candidates,
+            filter,
             predicate,
         }
     }
 
+    /// Returns the lookup tables of this document, building them if needed.
+    pub fn index(&self) -> &DocumentIndex {
+        self.index.get_or_init(|| DocumentIndex::new(self))
+    }
+
+    /// Returns the element with the id `id`, looked up in the tables of
+    /// `Document::index`. If several elements have it, returns the first in
+    /// document order.
+    pub fn by_id(&self, id: &str) -> Option<Node<'_>> {
+        self.nth(self.index().first_id(id)?)
+    }
+
+    /// Reports each element having the same id as an element before it in
+    /// document order, which is invalid HTML. The position is known if the document was parsed with
+    /// `ParseOptions::spans` enabled.
+    pub fn duplicate_ids(&self) -> Vec<Diagnostic> {
+        self.index()
+            .duplicate_ids()
+            .iter()
+            .map(|&index| {
+                let node = self.nth(index).unwrap();
+                Diagnostic {
+                    message: format!("Duplicate id \"{}\"", node.attr("id").unwrap()).into(),
+                    position: node.span().map(|span| span.start),
+                }
+            })
+            .collect()
+    }
+
+    /// Returns a `TreeBuilder` constructing a new `Document`.
+    pub fn builder() -> TreeBuilder {
+        TreeBuilder::new()
+    }
+
     /// Returns the `n`th node of the document as a `Some(Node)`, indexed from
     /// 0, or `None` if n is greater than or equal to the number of nodes.
-    pub fn nth(&self, n: usize) -> Option<Node> {
+    pub fn nth(&self, n: usize) -> Option<Node<'_>> {
         Node::new(self, n)
     }
 
+    /// Returns the `<template>` element whose contents have `node` at their
+    /// top level, if any.
+    pub(crate) fn template_of(&self, node: usize) -> Option<usize> {
+        if self.nodes[node].parent.is_some() {
+            return None;
+        }
+        let first = std::iter::successors(Some(node), |&index| self.nodes[index].prev).last()?;
+        self.templates.get(&first).copied()
+    }
+
+    /// Sets the first node of the contents of `template`.
+    pub(crate) fn set_template_contents(&mut self, template: usize, first: Option<usize>) {
+        if let Some(old) = self.nodes[template].template_contents {
+            self.templates.remove(&old);
+        }
+        if let Some(first) = first {
+            self.templates.insert(first, template);
+        }
+        self.nodes[template].template_contents = first;
+    }
+
+    /// Returns the first top-level node of the tree, if any.
+    pub(crate) fn first_root(&self) -> Option<usize> {
+        // Template contents have no parent either.
+        self.nodes.iter().position(|raw| {
+            raw.parent.is_none()
+                && raw.prev.is_none()
+                && !self.is_detached(raw.index)
+                && !self.templates.contains_key(&raw.index)
+        })
+    }
+
+    /// Returns the `n`th node of the document as a `Some(NodeMut)`, indexed
+    /// from 0, or `None` if n is greater than or equal to the number of nodes.
+    pub fn nth_mut(&mut self, n: usize) -> Option<NodeMut<'_>> {
+        NodeMut::new(self, n)
+    }
+
+    /// Reads and parses a `Document` from `readable`, which must be UTF-8.
+    #[cfg(not(feature = "encoding"))]
     pub fn from_read<R: io::Read>(mut readable: R) -> io::Result<Document> {
         let mut byte_tendril = ByteTendril::new();
         readable.read_to_tendril(&mut byte_tendril)?;
@@ -40,111 +180,222 @@ impl Document {
             )),
         }
     }
+
+    /// Reads and parses a `Document` from `readable`, detecting its character
+    /// encoding as described in `encoding::sniff`.
+    #[cfg(feature = "encoding")]
+    pub fn from_read<R: io::Read>(readable: R) -> io::Result<Document> {
+        Document::from_read_with_encoding(readable, None).map(|(document, _)| document)
+    }
+
+    /// Reads and parses a `Document` from `readable`, detecting its character
+    /// encoding, and returns it along with the encoding that was used.
+    ///
+    /// `transport` is an optional encoding label supplied by the transport
+    /// layer, e.g. the `charset` parameter of an HTTP `Content-Type` header.
+    #[cfg(feature = "encoding")]
+    pub fn from_read_with_encoding<R: io::Read>(
+        mut readable: R,
+        transport: Option<&str>,
+    ) -> io::Result<(Document, &'static encoding_rs::Encoding)> {
+        let mut bytes = Vec::new();
+        readable.read_to_end(&mut bytes)?;
+        let (string, encoding) = crate::encoding::decode(&bytes, transport);
+        Ok((Document::from(StrTendril::from(string)), encoding))
+    }
+
+    /// Parses `html` into a `Document` with the given options.
+    pub fn parse_with_options(html: &str, options: ParseOptions) -> Document {
+        if !options.spans {
+            return Document::from(html);
+        }
+        Document::parse(html, options, false).0
+    }
+
+    /// Parses `html` into a `Document`, also returning the errors reported by
+    /// the parser.
+    pub fn parse_with_diagnostics(html: &str) -> (Document, Vec<Diagnostic>) {
+        Document::parse(html, Default::default(), true)
+    }
+
+    fn parse(html: &str, options: ParseOptions, errors: bool) -> (Document, Vec<Diagnostic>) {
+        use html5ever::parse_document;
+        use html5ever::tokenizer::TokenizerOpts;
+
+        let tendril = StrTendril::from(html);
+        let mut sink = Sink::new(false).with_locator(tendril.clone(), options.spans);
+        if errors {
+            sink = sink.with_errors();
+        }
+        let opts = html5ever::ParseOpts {
+            tokenizer: TokenizerOpts {
+                exact_errors: errors,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut parser = parse_document(sink, opts);
+
+        // The tree builder is not told where in the input its tokens come from,
+        // so the input is fed in chunks ending before every `<` and after every
+        // `>`, which lets the sink locate each tag within the current chunk.
+        let mut start = 0;
+        let boundaries = html
+            .match_indices(['<', '>'])
+            .map(|(index, c)| if c == "<" { index } else { index + 1 })
+            .chain(Some(html.len()));
+        for end in boundaries {
+            if end > start {
+                parser.tokenizer.sink.sink.set_chunk(start, end);
+                parser.process(tendril.subtendril(start as u32, (end - start) as u32));
+                start = end;
+            }
+        }
+        parser.tokenizer.sink.sink.set_chunk(html.len(), html.len());
+
+        parser.finish()
+    }
+
+    /// Parses `html` as a fragment in the context of an HTML element named
+    /// `context`, as `innerHTML` would. The fragment's top-level nodes become
+    /// the root nodes of the returned `Document`; no `html`, `head` or `body`
+    /// elements are added.
+    pub fn from_fragment(html: &str, context: &str) -> Document {
+        use html5ever::parse_fragment;
+
+        let context = QualName::new(None, ns!(html), LocalName::from(context));
+        parse_fragment(Sink::new(true), Default::default(), context, vec![])
+            .one(StrTendril::from(html))
+            .0
+    }
 }
 
 impl From<StrTendril> for Document {
     /// Parses the given `StrTendril` into a `Document`.
     fn from(tendril: StrTendril) -> Document {
         use html5ever::parse_document;
-        use html5ever::tendril::stream::TendrilSink;
-        use markup5ever_rcdom::{Handle, NodeData, RcDom};
-
-        let mut document = Document { nodes: vec![] };
-
-        let rc_dom = parse_document(RcDom::default(), Default::default()).one(tendril);
-        recur(&mut document, &rc_dom.document, None, None);
-        return document;
-
-        fn recur(
-            document: &mut Document,
-            node: &Handle,
-            parent: Option<usize>,
-            prev: Option<usize>,
-        ) -> Option<usize> {
-            match node.data {
-                NodeData::Document => {
-                    let mut prev = None;
-                    for child in node.children.borrow().iter() {
-                        prev = recur(document, child, None, prev)
-                    }
-                    None
-                }
-                NodeData::Text { ref contents } => {
-                    let data = node::Data::Text(contents.borrow().clone());
-                    Some(append(document, data, parent, prev))
-                }
-                NodeData::Comment { ref contents } => {
-                    let data = node::Data::Comment(contents.clone());
-                    Some(append(document, data, parent, prev))
-                }
-                NodeData::Element {
-                    ref name,
-                    ref attrs,
-                    ..
-                } => {
-                    let name = name.clone();
-                    let attrs = attrs
-                        .borrow()
-                        .iter()
-                        .map(|attr| (attr.name.clone(), attr.value.clone()))
-                        .collect();
-                    let data = node::Data::Element(name, attrs);
-                    let index = append(document, data, parent, prev);
-                    let mut prev = None;
-                    for child in node.children.borrow().iter() {
-                        prev = recur(document, child, Some(index), prev)
-                    }
-                    Some(index)
-                }
-                _ => None,
-            }
+
+        parse_document(Sink::new(false), Default::default())
+            .one(tendril)
+            .0
+    }
+}
+
+/// Builds a `Document` incrementally from chunks of UTF-8 encoded bytes.
+///
+/// Invalid UTF-8 sequences are replaced with U+FFFD, and sequences split across
+/// chunks are handled correctly.
+pub struct DocumentBuilder {
+    parser: Utf8LossyDecoder<html5ever::Parser<Sink>>,
+}
+
+impl DocumentBuilder {
+    pub fn new() -> DocumentBuilder {
+        DocumentBuilder {
+            parser: html5ever::parse_document(Sink::new(false), Default::default()).from_utf8(),
         }
+    }
 
-        fn append(
-            document: &mut Document,
-            data: node::Data,
-            parent: Option<usize>,
-            prev: Option<usize>,
-        ) -> usize {
-            let index = document.nodes.len();
-
-            document.nodes.push(node::Raw {
-                index,
-                parent,
-                prev,
-                next: None,
-                first_child: None,
-                last_child: None,
-                data,
-            });
-
-            if let Some(parent) = parent {
-                let parent = &mut document.nodes[parent];
-                if parent.first_child.is_none() {
-                    parent.first_child = Some(index);
-                }
-                parent.last_child = Some(index);
-            }
+    /// Parses the next chunk of input.
+    pub fn feed(&mut self, chunk: &[u8]) {
+        self.parser.process(ByteTendril::from(chunk));
+    }
 
-            if let Some(prev) = prev {
-                document.nodes[prev].next = Some(index);
-            }
+    /// Finishes parsing and returns the `Document`.
+    pub fn finish(self) -> Document {
+        self.parser.finish().0
+    }
+}
 
-            index
+impl Default for DocumentBuilder {
+    fn default() -> DocumentBuilder {
+        DocumentBuilder::new()
+    }
+}
+
+impl fmt::Debug for DocumentBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DocumentBuilder").finish_non_exhaustive()
+    }
+}
+
+/// Allows streaming into a `DocumentBuilder` with `io::copy`.
+impl io::Write for DocumentBuilder {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.feed(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl From<Vec<node::Raw>> for Document {
+    /// Creates a `Document` from nodes linked to each other by index.
+    fn from(nodes: Vec<node::Raw>) -> Document {
+        let templates = nodes
+            .iter()
+            .filter_map(|raw| Some((raw.template_contents?, raw.index)))
+            .collect();
+        Document {
+            nodes,
+            spans: vec![],
+            detached: vec![],
+            templates,
+            index: Default::default(),
         }
     }
 }
 
-impl<'a> From<&'a str> for Document {
+impl From<&str> for Document {
     /// Parses the given `&str` into a `Document`.
     fn from(str: &str) -> Document {
         Document::from(StrTendril::from(str))
     }
 }
 
+fn append(
+    document: &mut Document,
+    data: node::Data,
+    parent: Option<usize>,
+    prev: Option<usize>,
+) -> usize {
+    let index = document.nodes.len();
+
+    document.nodes.push(node::Raw {
+        index,
+        parent,
+        prev,
+        next: None,
+        first_child: None,
+        last_child: None,
+        template_contents: None,
+        data,
+    });
+
+    if let Some(parent) = parent {
+        let parent = &mut document.nodes[parent];
+        if parent.first_child.is_none() {
+            parent.first_child = Some(index);
+        }
+        parent.last_child = Some(index);
+    }
+
+    if let Some(prev) = prev {
+        document.nodes[prev].next = Some(index);
+    }
+
+    index
+}
+
 pub struct Find<'a, P> {
     document: &'a Document,
     next: usize,
+    /// The indices of the only nodes which can match, from a `DocumentIndex`.
+    candidates: Option<Cow<'a, [usize]>>,
+    /// Rejects nodes lacking ancestors required by the predicate.
+    filter: Option<AncestorFilter>,
     predicate: P,
 }
 
@@ -168,9 +419,25 @@ impl<'a, P: Predicate> Iterator for Find<'a, P> {
     type Item = Node<'a>;
 
     fn next(&mut self) -> Option<Node<'a>> {
-        while self.next < self.document.nodes.len() {
-            let node = self.document.nth(self.next).unwrap();
+        let len = self
+            .candidates
+            .as_ref()
+            .map_or(self.document.nodes.len(), |candidates| candidates.len());
+        while self.next < len {
+            let index = self
+                .candidates
+                .as_ref()
+                .map_or(self.next, |candidates| candidates[self.next]);
+            let node = self.document.nth(index).unwrap();
             self.next += 1;
+            if node.is_detached() {
+                continue;
+            }
+            if let Some(ref mut filter) = self.filter {
+                if !filter.might_match(&node) {
+                    continue;
+                }
+            }
             if self.predicate.matches(&node) {
                 return Some(node);
             }
@@ -0,0 +1,250 @@
+//! A counting bloom filter of the names, ids and classes of the ancestors of
+//! the node being visited by `Document::find`. As in browser engines matching
+//! selectors right to left, it rejects most nodes whose `PredicateHint`
+//! requires ancestors they do not have, without walking their ancestors.
+
+use super::Document;
+use crate::node::Node;
+use crate::predicate::PredicateHint;
+
+const KEY_BITS: u32 = 12;
+const KEY_MASK: u32 = (1 << KEY_BITS) - 1;
+
+/// Tracks the ancestors of the nodes checked, in any order, and checks them
+/// against the ancestors required by a `PredicateHint`.
+#[derive(Clone, Debug)]
+pub(crate) struct AncestorFilter {
+    counters: Box<[u8]>,
+    /// The ancestors in the filter, outermost first, with the start of their
+    /// keys in `hashes`.
+    stack: Vec<(usize, usize)>,
+    hashes: Vec<u32>,
+    /// The last node checked, whose ancestors are those in `stack`.
+    last: Option<usize>,
+    /// The ancestors of the node being checked, innermost first.
+    chain: Vec<usize>,
+    /// The kinds of keys `required` has; others are not hashed.
+    kinds: Kinds,
+    /// The names of which the node must have one, if known.
+    names: Option<Vec<String>>,
+    required: Required,
+}
+
+impl AncestorFilter {
+    /// Returns `None` if `hint` requires nothing the filter can check.
+    pub(crate) fn new(hint: &PredicateHint) -> Option<AncestorFilter> {
+        let mut kinds = Kinds::default();
+        let required = Required::for_node(hint, &mut kinds);
+        if required.is_trivial() {
+            return None;
+        }
+        Some(AncestorFilter {
+            counters: vec![0; 1 << KEY_BITS].into_boxed_slice(),
+            stack: vec![],
+            hashes: vec![],
+            last: None,
+            chain: vec![],
+            kinds,
+            names: names(hint),
+            required,
+        })
+    }
+
+    /// Returns `false` if `node` certainly does not match the hint.
+    pub(crate) fn might_match(&mut self, node: &Node) -> bool {
+        if let Some(ref names) = self.names {
+            match node.name() {
+                Some(name) if names.iter().any(|n| n == name) => {}
+                _ => return false,
+            }
+        }
+
+        let document = node.document();
+        let parent = node.raw().parent;
+        let last = self.last.replace(node.index());
+        if let Some(parent) = parent.filter(|&parent| Some(parent) == last) {
+            self.push(document, parent);
+        }
+        if self.stack.last().map(|&(index, _)| index) == parent {
+            return self.required.is_satisfied(&self.counters);
+        }
+
+        // Keep the ancestors shared with the previous node checked, which
+        // are most of them when nodes are checked in document order.
+        let mut chain = std::mem::take(&mut self.chain);
+        chain.clear();
+        chain.extend(std::iter::successors(parent, |&index| {
+            document.nodes[index].parent
+        }));
+        let shared = self
+            .stack
+            .iter()
+            .zip(chain.iter().rev())
+            .take_while(|((ancestor, _), index)| ancestor == *index)
+            .count();
+        while self.stack.len() > shared {
+            self.pop();
+        }
+        for &index in chain.iter().rev().skip(shared) {
+            self.push(document, index);
+        }
+        self.chain = chain;
+
+        self.required.is_satisfied(&self.counters)
+    }
+
+    fn push(&mut self, document: &Document, index: usize) {
+        let start = self.hashes.len();
+        let node = document.nth(index).unwrap();
+        if self.kinds.names {
+            self.hashes.extend(node.name().map(|name| hash(b'n', name)));
+        }
+        if self.kinds.ids {
+            self.hashes.extend(node.attr("id").map(|id| hash(b'#', id)));
+        }
+        if self.kinds.classes {
+            let classes = node
+                .attr("class")
+                .into_iter()
+                .flat_map(str::split_whitespace);
+            self.hashes.extend(classes.map(|class| hash(b'.', class)));
+        }
+        for &hash in &self.hashes[start..] {
+            for key in keys(hash) {
+                // Saturated counters are never decremented.
+                self.counters[key] = self.counters[key].saturating_add(1);
+            }
+        }
+        self.stack.push((index, start));
+    }
+
+    fn pop(&mut self) {
+        let (_, start) = self.stack.pop().unwrap();
+        for hash in self.hashes.drain(start..) {
+            for key in keys(hash) {
+                if self.counters[key] != u8::MAX {
+                    self.counters[key] -= 1;
+                }
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+struct Kinds {
+    names: bool,
+    ids: bool,
+    classes: bool,
+}
+
+/// The hashed keys which a node or its ancestors must have.
+#[derive(Clone, Debug)]
+struct Required {
+    /// Hashes of the name, id and classes of the node.
+    hashes: Vec<u32>,
+    /// If not empty, one of these must be satisfied.
+    any_of: Vec<Required>,
+    /// Each must be satisfied by an ancestor.
+    ancestors: Vec<Required>,
+}
+
+impl Required {
+    /// The requirements of `hint` on the ancestors of a node, ignoring those
+    /// on the node itself.
+    fn for_node(hint: &PredicateHint, kinds: &mut Kinds) -> Required {
+        Required::normalized(
+            vec![],
+            hint.any_of
+                .iter()
+                .map(|hint| Required::for_node(hint, kinds))
+                .collect(),
+            hint.ancestors
+                .iter()
+                .map(|hint| Required::for_ancestor(hint, kinds))
+                .collect(),
+        )
+    }
+
+    /// The requirements of `hint` on an ancestor.
+    fn for_ancestor(hint: &PredicateHint, kinds: &mut Kinds) -> Required {
+        kinds.names |= hint.name.is_some();
+        kinds.ids |= hint.id.is_some();
+        kinds.classes |= !hint.classes.is_empty();
+        let hashes = hint
+            .name
+            .map(|name| hash(b'n', name))
+            .into_iter()
+            .chain(hint.id.map(|id| hash(b'#', id)))
+            .chain(hint.classes.iter().map(|class| hash(b'.', class)))
+            .collect();
+        Required::normalized(
+            hashes,
+            hint.any_of
+                .iter()
+                .map(|hint| Required::for_ancestor(hint, kinds))
+                .collect(),
+            hint.ancestors
+                .iter()
+                .map(|hint| Required::for_ancestor(hint, kinds))
+                .collect(),
+        )
+    }
+
+    fn normalized(hashes: Vec<u32>, any_of: Vec<Required>, ancestors: Vec<Required>) -> Required {
+        // An alternative which always holds makes the others irrelevant.
+        let any_of = if any_of.iter().any(Required::is_trivial) {
+            vec![]
+        } else {
+            any_of
+        };
+        Required {
+            hashes,
+            any_of,
+            ancestors: ancestors.into_iter().filter(|a| !a.is_trivial()).collect(),
+        }
+    }
+
+    fn is_trivial(&self) -> bool {
+        self.hashes.is_empty() && self.any_of.is_empty() && self.ancestors.is_empty()
+    }
+
+    /// Returns `false` if some key is certainly missing from `counters`.
+    fn is_satisfied(&self, counters: &[u8]) -> bool {
+        self.hashes
+            .iter()
+            .all(|&hash| keys(hash).iter().all(|&key| counters[key] != 0))
+            && (self.any_of.is_empty() || self.any_of.iter().any(|r| r.is_satisfied(counters)))
+            && self.ancestors.iter().all(|r| r.is_satisfied(counters))
+    }
+}
+
+/// The names of which a node satisfying `hint` has one, if known.
+fn names(hint: &PredicateHint) -> Option<Vec<String>> {
+    match hint.name {
+        Some(name) => Some(vec![name.into()]),
+        None if hint.any_of.is_empty() => None,
+        None => hint
+            .any_of
+            .iter()
+            .map(names)
+            .collect::<Option<Vec<_>>>()
+            .map(|names| names.concat()),
+    }
+}
+
+/// The two counters of a hash.
+fn keys(hash: u32) -> [usize; 2] {
+    [
+        (hash & KEY_MASK) as usize,
+        ((hash >> KEY_BITS) & KEY_MASK) as usize,
+    ]
+}
+
+/// FNV-1a of `kind` followed by `key`.
+fn hash(kind: u8, key: &str) -> u32 {
+    std::iter::once(kind)
+        .chain(key.bytes())
+        .fold(0x811c_9dc5, |hash, byte| {
+            (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+        })
+}
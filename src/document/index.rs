@@ -0,0 +1,126 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use super::Document;
+use crate::predicate::PredicateHint;
+
+/// Lookup tables from ids, class names and local names to the elements having
+/// them, built by `Document::index`.
+///
+/// Once built, `Document::find` and `Selection::find` use the index to visit
+/// only the candidate nodes of predicates whose `Predicate::hint` requires a
+/// name, class or id, such as `Name`, `Class`, `Attr("id", _)` and CSS
+/// selectors made of these.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DocumentIndex {
+    ids: HashMap<String, Vec<usize>>,
+    classes: HashMap<String, Vec<usize>>,
+    names: HashMap<String, Vec<usize>>,
+    /// The first element in document order with each id.
+    first_ids: HashMap<String, usize>,
+    /// The elements having the id of an element before them in document
+    /// order, in document order.
+    duplicate_ids: Vec<usize>,
+}
+
+/// A key of a `DocumentIndex`, which every node matched by a predicate has.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum IndexKey<'a> {
+    Id(&'a str),
+    Class(&'a str),
+    Name(&'a str),
+}
+
+impl DocumentIndex {
+    /// Indexes the elements of `document` which are not detached.
+    pub fn new(document: &Document) -> DocumentIndex {
+        let mut index = DocumentIndex::default();
+        // Visited in document order, so that ids are first in that order.
+        for i in document.tree_order() {
+            let node = document.nth(i).unwrap();
+            let name = match node.name() {
+                Some(name) => name,
+                None => continue,
+            };
+            let push = |map: &mut HashMap<String, Vec<usize>>, key: &str| {
+                let indices = map.entry(key.into()).or_default();
+                if indices.last() != Some(&i) {
+                    indices.push(i);
+                }
+            };
+            push(&mut index.names, name);
+            if let Some(id) = node.attr("id") {
+                push(&mut index.ids, id);
+                if index.first_ids.contains_key(id) {
+                    index.duplicate_ids.push(i);
+                } else {
+                    index.first_ids.insert(id.into(), i);
+                }
+            }
+            for class in node
+                .attr("class")
+                .into_iter()
+                .flat_map(str::split_whitespace)
+            {
+                push(&mut index.classes, class);
+            }
+        }
+        for indices in index
+            .ids
+            .values_mut()
+            .chain(index.classes.values_mut())
+            .chain(index.names.values_mut())
+        {
+            indices.sort_unstable();
+        }
+        index
+    }
+
+    /// Get the indices of the elements with the given key, in ascending order.
+    pub fn get(&self, key: IndexKey) -> &[usize] {
+        let (map, key) = match key {
+            IndexKey::Id(id) => (&self.ids, id),
+            IndexKey::Class(class) => (&self.classes, class),
+            IndexKey::Name(name) => (&self.names, name),
+        };
+        map.get(key).map_or(&[], Vec::as_slice)
+    }
+
+    /// Get the index of the first element in document order with the given
+    /// id.
+    pub(crate) fn first_id(&self, id: &str) -> Option<usize> {
+        self.first_ids.get(id).copied()
+    }
+
+    /// Get the indices, in document order, of the elements having the id of
+    /// an element before them.
+    pub(crate) fn duplicate_ids(&self) -> &[usize] {
+        &self.duplicate_ids
+    }
+
+    /// Get the indices, in ascending order, of the only elements which can
+    /// satisfy `hint`, or `None` if the index cannot narrow them down.
+    pub fn candidates(&self, hint: &PredicateHint) -> Option<Cow<'_, [usize]>> {
+        // The shortest list of the keys the hint requires.
+        let keys = hint
+            .id
+            .map(IndexKey::Id)
+            .into_iter()
+            .chain(hint.classes.iter().map(|&class| IndexKey::Class(class)))
+            .chain(hint.name.map(IndexKey::Name));
+        if let Some(candidates) = keys.map(|key| self.get(key)).min_by_key(|list| list.len()) {
+            return Some(Cow::Borrowed(candidates));
+        }
+
+        if hint.any_of.is_empty() {
+            return None;
+        }
+        let mut candidates = vec![];
+        for hint in &hint.any_of {
+            candidates.extend_from_slice(&self.candidates(hint)?);
+        }
+        candidates.sort_unstable();
+        candidates.dedup();
+        Some(Cow::Owned(candidates))
+    }
+}
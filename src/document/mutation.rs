@@ -0,0 +1,409 @@
+//! Methods changing the tree of a `Document`.
+//!
+//! Nodes are referred to by index. New nodes are created detached and can then
+//! be inserted anywhere in the tree; inserting a node which is already in the
+//! tree moves it. Detached and removed nodes stay in `Document::nodes`, marked
+//! as detached, so the indices of other nodes never change until
+//! `Document::compact` is called.
+
+use html5ever::{namespace_url, ns, LocalName, QualName};
+
+use super::Document;
+use crate::node::{self, Data};
+
+impl Document {
+    /// Creates a detached HTML element named `name`, returning its index.
+    pub fn create_element(&mut self, name: &str) -> usize {
+        let name = QualName::new(None, ns!(html), LocalName::from(name));
+        self.create(Data::Element(name, vec![]))
+    }
+
+    /// Creates a detached text node, returning its index.
+    pub fn create_text(&mut self, text: &str) -> usize {
+        self.create(Data::Text(node::Str::from(text)))
+    }
+
+    fn create(&mut self, data: Data) -> usize {
+        let index = self.nodes.len();
+        self.nodes.push(node::Raw {
+            index,
+            parent: None,
+            prev: None,
+            next: None,
+            first_child: None,
+            last_child: None,
+            template_contents: None,
+            data,
+        });
+        self.set_detached(index, true);
+        if !self.spans.is_empty() {
+            self.spans.push(None);
+        }
+        index
+    }
+
+    /// Rewrites `nodes` in document order, dropping detached nodes and
+    /// renumbering the rest. Returns the new index of each old index, or `None`
+    /// for dropped nodes.
+    pub fn compact(&mut self) -> Vec<Option<usize>> {
+        self.index.take();
+        let order = self.tree_order();
+
+        let mut mapping = vec![None; self.nodes.len()];
+        for (new, &old) in order.iter().enumerate() {
+            mapping[old] = Some(new);
+        }
+        let map = |index: Option<usize>| index.map(|index| mapping[index].unwrap());
+
+        let mut nodes = std::mem::take(&mut self.nodes)
+            .into_iter()
+            .map(Some)
+            .collect::<Vec<_>>();
+        self.nodes = order
+            .iter()
+            .enumerate()
+            .map(|(new, &old)| {
+                let raw = nodes[old].take().unwrap();
+                node::Raw {
+                    index: new,
+                    parent: map(raw.parent),
+                    prev: map(raw.prev),
+                    next: map(raw.next),
+                    first_child: map(raw.first_child),
+                    last_child: map(raw.last_child),
+                    template_contents: map(raw.template_contents),
+                    data: raw.data,
+                }
+            })
+            .collect();
+        if !self.spans.is_empty() {
+            self.spans = order.iter().map(|&old| self.spans[old]).collect();
+        }
+        self.detached.clear();
+        self.templates = self
+            .nodes
+            .iter()
+            .filter_map(|raw| Some((raw.template_contents?, raw.index)))
+            .collect();
+
+        mapping
+    }
+
+    /// Returns the indices of the nodes in the tree in document order.
+    pub(crate) fn tree_order(&self) -> Vec<usize> {
+        let first = self.first_root();
+        let mut order = Vec::with_capacity(self.nodes.len());
+        let mut stack = vec![];
+        for root in std::iter::successors(first, |&index| self.nodes[index].next) {
+            stack.push(root);
+            while let Some(index) = stack.pop() {
+                order.push(index);
+                let raw = &self.nodes[index];
+                // Template contents come before any children, as when parsed.
+                let start = stack.len();
+                for first in [raw.template_contents, raw.first_child] {
+                    let mut child = first;
+                    while let Some(index) = child {
+                        stack.push(index);
+                        child = self.nodes[index].next;
+                    }
+                }
+                stack[start..].reverse();
+            }
+        }
+        order
+    }
+
+    /// Copies the nodes of `other` into this document, detached, returning the
+    /// indices of its top-level nodes in order.
+    pub(crate) fn import(&mut self, other: Document) -> Vec<usize> {
+        let offset = self.nodes.len();
+        let shift = |index: Option<usize>| index.map(|index| index + offset);
+        let first = other.first_root();
+        let roots = std::iter::successors(first, |&index| other.nodes[index].next)
+            .map(|index| index + offset)
+            .collect::<Vec<_>>();
+
+        self.templates.extend(
+            other
+                .templates
+                .iter()
+                .map(|(&first, &template)| (first + offset, template + offset)),
+        );
+        for raw in other.nodes {
+            self.nodes.push(node::Raw {
+                index: raw.index + offset,
+                parent: shift(raw.parent),
+                prev: shift(raw.prev),
+                next: shift(raw.next),
+                first_child: shift(raw.first_child),
+                last_child: shift(raw.last_child),
+                template_contents: shift(raw.template_contents),
+                data: raw.data,
+            });
+            if !self.spans.is_empty() {
+                self.spans.push(None);
+            }
+        }
+        self.detached.resize(offset, false);
+        self.detached.resize(self.nodes.len(), true);
+
+        // Unlink the top-level nodes from each other so they can be inserted.
+        for &root in &roots {
+            self.nodes[root].prev = None;
+            self.nodes[root].next = None;
+        }
+        roots
+    }
+
+    /// Inserts `node` as the last child of the element `parent`.
+    ///
+    /// # Panics
+    ///
+    /// If `parent` is not an element, or is `node` or one of its descendants.
+    pub fn append_child(&mut self, parent: usize, node: usize) {
+        self.check_parent(parent, node);
+        self.detach(node);
+        let prev = self.nodes[parent].last_child;
+        self.link(node, Some(parent), prev, None);
+    }
+
+    /// Inserts `node` at the end of the contents of the `<template>` element
+    /// `template`.
+    pub(crate) fn append_template_contents(&mut self, template: usize, node: usize) {
+        self.check_sibling(template, node);
+        self.detach(node);
+        let last = std::iter::successors(self.nodes[template].template_contents, |&index| {
+            self.nodes[index].next
+        })
+        .last();
+        match last {
+            Some(last) => self.link(node, None, Some(last), None),
+            None => {
+                self.index.take();
+                self.set_template_contents(template, Some(node));
+                self.set_detached(node, self.is_detached(template));
+            }
+        }
+    }
+
+    /// Inserts `node` as the first child of the element `parent`.
+    ///
+    /// # Panics
+    ///
+    /// If `parent` is not an element, or is `node` or one of its descendants.
+    pub fn prepend_child(&mut self, parent: usize, node: usize) {
+        self.check_parent(parent, node);
+        self.detach(node);
+        let next = self.nodes[parent].first_child;
+        self.link(node, Some(parent), None, next);
+    }
+
+    /// Inserts `node` as the previous sibling of `sibling`.
+    ///
+    /// # Panics
+    ///
+    /// If `sibling` is `node` or one of its descendants.
+    pub fn insert_before(&mut self, sibling: usize, node: usize) {
+        self.check_sibling(sibling, node);
+        self.detach(node);
+        let raw = &self.nodes[sibling];
+        let (parent, prev) = (raw.parent, raw.prev);
+        self.link(node, parent, prev, Some(sibling));
+    }
+
+    /// Inserts `node` as the next sibling of `sibling`.
+    ///
+    /// # Panics
+    ///
+    /// If `sibling` is `node` or one of its descendants.
+    pub fn insert_after(&mut self, sibling: usize, node: usize) {
+        self.check_sibling(sibling, node);
+        self.detach(node);
+        let raw = &self.nodes[sibling];
+        let (parent, next) = (raw.parent, raw.next);
+        self.link(node, parent, Some(sibling), next);
+    }
+
+    /// Replaces `old` with `new`, detaching `old`.
+    ///
+    /// # Panics
+    ///
+    /// If `old` is `new` or one of its descendants.
+    pub fn replace(&mut self, old: usize, new: usize) {
+        self.insert_before(old, new);
+        self.detach(old);
+    }
+
+    /// Detaches `node` and its descendants from the tree. They can be inserted
+    /// again later.
+    pub fn detach(&mut self, node: usize) {
+        let raw = &self.nodes[node];
+        let (parent, prev, next) = (raw.parent, raw.prev, raw.next);
+        self.index.take();
+
+        match (prev, parent) {
+            (Some(prev), _) => self.nodes[prev].next = next,
+            (None, Some(parent)) => {
+                let parent = &mut self.nodes[parent];
+                if parent.first_child == Some(node) {
+                    parent.first_child = next;
+                }
+            }
+            (None, None) => {
+                if let Some(template) = self.template_of(node) {
+                    self.set_template_contents(template, next);
+                }
+            }
+        }
+        match (next, parent) {
+            (Some(next), _) => self.nodes[next].prev = prev,
+            (None, Some(parent)) => {
+                let parent = &mut self.nodes[parent];
+                if parent.last_child == Some(node) {
+                    parent.last_child = prev;
+                }
+            }
+            (None, None) => {}
+        }
+
+        let raw = &mut self.nodes[node];
+        raw.parent = None;
+        raw.prev = None;
+        raw.next = None;
+        self.set_detached(node, true);
+    }
+
+    /// Removes `node` and its descendants from the tree. Their slots in
+    /// `nodes` are kept but are no longer visible through the tree or `find`.
+    pub fn remove(&mut self, node: usize) {
+        self.detach(node);
+    }
+
+    /// Sets the value of the attribute `name` of the element `node`, replacing
+    /// any existing value.
+    ///
+    /// # Panics
+    ///
+    /// If `node` is not an element.
+    pub fn set_attr(&mut self, node: usize, name: &str, value: &str) {
+        self.index.take();
+        let attrs = match self.nodes[node].data {
+            Data::Element(_, ref mut attrs) => attrs,
+            _ => panic!("node {} is not an element", node),
+        };
+        let value = node::Str::from(value);
+        match attrs.iter_mut().find(|(attr, _)| &*attr.local == name) {
+            Some((_, existing)) => *existing = value,
+            None => attrs.push((QualName::new(None, ns!(), LocalName::from(name)), value)),
+        }
+    }
+
+    /// Removes the attribute `name` of the element `node`, returning whether
+    /// it was present.
+    ///
+    /// # Panics
+    ///
+    /// If `node` is not an element.
+    pub fn remove_attr(&mut self, node: usize, name: &str) -> bool {
+        self.index.take();
+        let attrs = match self.nodes[node].data {
+            Data::Element(_, ref mut attrs) => attrs,
+            _ => panic!("node {} is not an element", node),
+        };
+        let len = attrs.len();
+        attrs.retain(|(attr, _)| &*attr.local != name);
+        attrs.len() != len
+    }
+
+    /// Links the detached `node` between `prev` and `next` under `parent`.
+    fn link(
+        &mut self,
+        node: usize,
+        parent: Option<usize>,
+        prev: Option<usize>,
+        next: Option<usize>,
+    ) {
+        // Inserting before the first node of template contents.
+        let template = match (prev, parent, next) {
+            (None, None, Some(next)) => self.template_of(next),
+            _ => None,
+        };
+        let raw = &mut self.nodes[node];
+        raw.parent = parent;
+        raw.prev = prev;
+        raw.next = next;
+        self.index.take();
+
+        match (prev, parent) {
+            (Some(prev), _) => self.nodes[prev].next = Some(node),
+            (None, Some(parent)) => self.nodes[parent].first_child = Some(node),
+            (None, None) => {
+                if let Some(template) = template {
+                    self.set_template_contents(template, Some(node));
+                }
+            }
+        }
+        match (next, parent) {
+            (Some(next), _) => self.nodes[next].prev = Some(node),
+            (None, Some(parent)) => {
+                let parent = &mut self.nodes[parent];
+                if parent.last_child == prev {
+                    parent.last_child = Some(node);
+                }
+            }
+            (None, None) => {}
+        }
+
+        let detached = match (parent, prev.or(next)) {
+            (Some(parent), _) => self.is_detached(parent),
+            (None, Some(sibling)) => self.is_detached(sibling),
+            (None, None) => true,
+        };
+        self.set_detached(node, detached);
+    }
+
+    /// Whether `node` has been detached from the tree.
+    pub(crate) fn is_detached(&self, node: usize) -> bool {
+        self.detached.get(node).copied().unwrap_or(false)
+    }
+
+    /// Sets `detached` on `node` and all its descendants, including template
+    /// contents.
+    fn set_detached(&mut self, node: usize, detached: bool) {
+        let mut stack = vec![node];
+        while let Some(index) = stack.pop() {
+            if self.detached.len() <= index {
+                self.detached.resize(index + 1, false);
+            }
+            self.detached[index] = detached;
+            let raw = &self.nodes[index];
+            for first in [raw.first_child, raw.template_contents] {
+                let mut child = first;
+                while let Some(index) = child {
+                    stack.push(index);
+                    child = self.nodes[index].next;
+                }
+            }
+        }
+    }
+
+    fn check_parent(&self, parent: usize, node: usize) {
+        if !matches!(self.nodes[parent].data, Data::Element(..)) {
+            panic!("node {} is not an element", parent);
+        }
+        self.check_sibling(parent, node);
+    }
+
+    /// Panics if `target` is `node` or one of its descendants, including
+    /// template contents.
+    fn check_sibling(&self, target: usize, node: usize) {
+        let mut current = Some(target);
+        while let Some(index) = current {
+            if index == node {
+                panic!("node {} cannot be inserted into itself", node);
+            }
+            current = self.nodes[index].parent.or_else(|| self.template_of(index));
+        }
+    }
+}
@@ -0,0 +1,551 @@
+//! A `TreeSink` building a `Document` directly, without an intermediate DOM.
+
+use std::borrow::Cow;
+
+use html5ever::tendril::StrTendril;
+use html5ever::tree_builder::{ElementFlags, NodeOrText, QuirksMode, TreeSink};
+use html5ever::{Attribute, ExpandedName, QualName};
+
+use super::{append, Diagnostic, Document};
+use crate::node::{self, Position, Span};
+
+/// The handle of the document node.
+const DOCUMENT: usize = 0;
+
+/// A node under construction. Nodes are linked the same way as `node::Raw`,
+/// but may be created, moved and detached in any order by the tree builder.
+#[derive(Debug)]
+struct Entry {
+    parent: Option<usize>,
+    prev: Option<usize>,
+    next: Option<usize>,
+    first_child: Option<usize>,
+    last_child: Option<usize>,
+    /// `None` for the document node and template contents.
+    data: Option<node::Data>,
+    template_contents: Option<usize>,
+    integration_point: bool,
+}
+
+impl Entry {
+    fn new(data: Option<node::Data>) -> Entry {
+        Entry {
+            parent: None,
+            prev: None,
+            next: None,
+            first_child: None,
+            last_child: None,
+            data,
+            template_contents: None,
+            integration_point: false,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Sink {
+    entries: Vec<Entry>,
+    /// Whether the input is parsed as a fragment, in which case the tree
+    /// builder wraps its nodes in an `html` element which is not kept.
+    fragment: bool,
+    locator: Option<Locator>,
+    /// Whether the spans computed by `locator` are kept in the `Document`.
+    spans: bool,
+    /// The parse errors reported so far, if they are being collected.
+    errors: Option<Vec<Diagnostic>>,
+}
+
+/// Tracks the source locations of nodes while parsing. The input is fed to the
+/// parser in chunks ending before every `<` and after every `>`, so a tag ends
+/// where the chunk being processed when its node is created ends.
+#[derive(Debug)]
+struct Locator {
+    source: StrTendril,
+    /// The byte offsets at which lines start.
+    lines: Vec<usize>,
+    /// The start and end of the chunk being processed.
+    chunk: (usize, usize),
+    /// The offset in the current chunk at which the next text is expected.
+    text: usize,
+    /// The end of the last located tag; later tags cannot start before it.
+    floor: usize,
+    /// The spans of the entries, by handle.
+    spans: Vec<Option<Span>>,
+    /// The last position computed. Offsets mostly increase, so the column of
+    /// a later offset on the same line is counted from here.
+    last: Position,
+}
+
+impl Locator {
+    fn position(&mut self, offset: usize) -> Position {
+        let line = self.lines.partition_point(|&start| start <= offset);
+        let (start, column) = if line == self.last.line && offset >= self.last.offset {
+            (self.last.offset, self.last.column)
+        } else {
+            (self.lines[line - 1], 1)
+        };
+        self.last = Position {
+            offset,
+            line,
+            column: column + self.source[start..offset].chars().count(),
+        };
+        self.last
+    }
+
+    fn span(&mut self, start: usize, end: usize) -> Span {
+        Span {
+            start: self.position(start),
+            end: self.position(end),
+        }
+    }
+
+    /// Locates markup ending at the end of the current chunk, starting with a
+    /// `<` for which `starts` returns true.
+    fn markup(&mut self, starts: impl Fn(&str) -> bool) -> Option<Span> {
+        let end = self.chunk.1;
+        if !self.source[..end].ends_with('>') {
+            return None;
+        }
+        let region = &self.source[self.floor..end];
+        let start = self.floor
+            + region
+                .rmatch_indices('<')
+                .find(|&(index, _)| starts(&region[index..]))?
+                .0;
+        self.floor = end;
+        Some(self.span(start, end))
+    }
+
+    /// Checks whether `text` from the parser comes from the current chunk.
+    /// Text buffered by the parser, such as table text which may be foster
+    /// parented, is only passed on when a later tag is processed.
+    fn in_chunk(&mut self, text: &str) -> bool {
+        let (start, end) = self.chunk;
+        if start == end {
+            return false;
+        }
+        if !self.source[start..end].starts_with('<') {
+            // Text is processed in its own chunk, but character references
+            // and newlines may make it differ from the source.
+            return true;
+        }
+        // Raw text, or text after a stray `<`, must match the source exactly.
+        let text_end = self.text + text.len();
+        if text_end <= end && self.source.get(self.text..text_end) == Some(text) {
+            self.text = text_end;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Converts a string from the parser into the type stored in `node::Data`.
+fn to_str(tendril: StrTendril) -> node::Str {
+    String::from(&*tendril)
+}
+
+/// Checks if `source` starts with `prefix`, ignoring ASCII case.
+fn starts_with_ignore_case(source: &str, prefix: &str) -> bool {
+    source
+        .as_bytes()
+        .get(..prefix.len())
+        .is_some_and(|start| start.eq_ignore_ascii_case(prefix.as_bytes()))
+}
+
+impl Sink {
+    pub(crate) fn new(fragment: bool) -> Sink {
+        Sink {
+            entries: vec![Entry::new(None)],
+            fragment,
+            locator: None,
+            spans: false,
+            errors: None,
+        }
+    }
+
+    /// Locates parse errors in `source`, which must be fed to the parser as
+    /// described in `Locator`. Nodes are only located, and their spans kept in
+    /// the `Document`, if `spans` is true.
+    pub(crate) fn with_locator(mut self, source: StrTendril, spans: bool) -> Sink {
+        let lines = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(index, _)| index + 1))
+            .collect();
+        self.locator = Some(Locator {
+            source,
+            lines,
+            chunk: (0, 0),
+            text: 0,
+            floor: 0,
+            spans: self.entries.iter().map(|_| None).collect(),
+            last: Position {
+                offset: 0,
+                line: 1,
+                column: 1,
+            },
+        });
+        self.spans = spans;
+        self
+    }
+
+    /// Collects parse errors, returned by `finish`.
+    pub(crate) fn with_errors(mut self) -> Sink {
+        self.errors = Some(vec![]);
+        self
+    }
+
+    /// Sets the chunk of the source about to be processed.
+    pub(crate) fn set_chunk(&mut self, start: usize, end: usize) {
+        if let Some(ref mut locator) = self.locator {
+            locator.chunk = (start, end);
+            locator.text = start;
+        }
+    }
+
+    fn push(&mut self, entry: Entry) -> usize {
+        self.entries.push(entry);
+        if let (true, Some(locator)) = (self.spans, &mut self.locator) {
+            locator.spans.push(None);
+        }
+        self.entries.len() - 1
+    }
+
+    /// Pushes a node located by `Locator::markup`.
+    fn push_markup(&mut self, data: node::Data, starts: impl Fn(&str) -> bool) -> usize {
+        let handle = self.push(Entry::new(Some(data)));
+        if let (true, Some(locator)) = (self.spans, &mut self.locator) {
+            locator.spans[handle] = locator.markup(starts);
+        }
+        handle
+    }
+
+    fn text(&mut self, text: StrTendril) -> usize {
+        let located = match (self.spans, &mut self.locator) {
+            (true, Some(locator)) => locator.in_chunk(&text),
+            _ => false,
+        };
+        let handle = self.push(Entry::new(Some(node::Data::Text(to_str(text)))));
+        if let (true, Some(locator)) = (located, &mut self.locator) {
+            let (start, end) = locator.chunk;
+            locator.spans[handle] = Some(locator.span(start, end));
+        }
+        handle
+    }
+
+    /// Appends `text` to the node `target` if it is a text node.
+    fn append_to_text(&mut self, target: Option<usize>, text: &StrTendril) -> bool {
+        let target = match target {
+            Some(target) => target,
+            None => return false,
+        };
+        match self.entries[target].data {
+            Some(node::Data::Text(ref mut contents)) => contents.push_str(text),
+            _ => return false,
+        }
+        if let (true, Some(locator)) = (self.spans, &mut self.locator) {
+            let start = locator.spans[target].map(|span| span.start);
+            locator.spans[target] = match start {
+                Some(start) if locator.in_chunk(text) => Some(Span {
+                    start,
+                    end: locator.position(locator.chunk.1),
+                }),
+                _ => None,
+            };
+        }
+        true
+    }
+
+    fn detach(&mut self, target: usize) {
+        let Entry {
+            parent, prev, next, ..
+        } = self.entries[target];
+
+        if let Some(parent) = parent {
+            let parent = &mut self.entries[parent];
+            if parent.first_child == Some(target) {
+                parent.first_child = next;
+            }
+            if parent.last_child == Some(target) {
+                parent.last_child = prev;
+            }
+        }
+        if let Some(prev) = prev {
+            self.entries[prev].next = next;
+        }
+        if let Some(next) = next {
+            self.entries[next].prev = prev;
+        }
+
+        let entry = &mut self.entries[target];
+        entry.parent = None;
+        entry.prev = None;
+        entry.next = None;
+    }
+
+    fn append_child(&mut self, parent: usize, child: usize) {
+        self.detach(child);
+
+        let prev = self.entries[parent].last_child;
+        if let Some(prev) = prev {
+            self.entries[prev].next = Some(child);
+        } else {
+            self.entries[parent].first_child = Some(child);
+        }
+        self.entries[parent].last_child = Some(child);
+
+        let entry = &mut self.entries[child];
+        entry.parent = Some(parent);
+        entry.prev = prev;
+    }
+
+    fn insert_before(&mut self, sibling: usize, child: usize) {
+        self.detach(child);
+
+        let Entry { parent, prev, .. } = self.entries[sibling];
+        if let Some(prev) = prev {
+            self.entries[prev].next = Some(child);
+        } else if let Some(parent) = parent {
+            self.entries[parent].first_child = Some(child);
+        }
+        self.entries[sibling].prev = Some(child);
+
+        let entry = &mut self.entries[child];
+        entry.parent = parent;
+        entry.prev = prev;
+        entry.next = Some(sibling);
+    }
+}
+
+impl TreeSink for Sink {
+    type Handle = usize;
+    type Output = (Document, Vec<Diagnostic>);
+
+    /// Copies the nodes reachable from the document node into a `Document` in
+    /// document order.
+    fn finish(mut self) -> (Document, Vec<Diagnostic>) {
+        let mut document = Document::from(Vec::with_capacity(self.entries.len()));
+
+        let mut root = DOCUMENT;
+        if self.fragment {
+            if let Some(html) = self.entries[DOCUMENT].first_child {
+                root = html;
+            }
+        }
+
+        // The levels of the tree above `current`: the handle whose children
+        // (or template contents) are being visited, its index in `document`,
+        // and whether they are template contents.
+        let mut stack: Vec<(usize, Option<usize>, bool)> = vec![(root, None, false)];
+        let mut prev = None;
+        let mut current = self.entries[root].first_child;
+
+        loop {
+            let handle = match current {
+                Some(handle) => handle,
+                None => match stack.pop() {
+                    Some((handle, index, _)) if handle != root => {
+                        prev = index;
+                        current = self.entries[handle].next;
+                        continue;
+                    }
+                    _ => break,
+                },
+            };
+
+            let data = match self.entries[handle].data.take() {
+                Some(data) => data,
+                None => {
+                    current = self.entries[handle].next;
+                    continue;
+                }
+            };
+
+            let index = match stack.last() {
+                // Template contents are reached from the template but have no
+                // parent, as they are not its children.
+                Some(&(_, Some(template), true)) => {
+                    let index = append(&mut document, data, None, prev);
+                    if prev.is_none() {
+                        document.set_template_contents(template, Some(index));
+                    }
+                    index
+                }
+                Some(&(_, parent, _)) => append(&mut document, data, parent, prev),
+                None => unreachable!(),
+            };
+            if let (true, Some(locator)) = (self.spans, &self.locator) {
+                document.spans.push(locator.spans[handle]);
+            }
+
+            let contents = self.entries[handle]
+                .template_contents
+                .and_then(|contents| self.entries[contents].first_child);
+            if let Some(first) = contents {
+                stack.push((handle, Some(index), true));
+                prev = None;
+                current = Some(first);
+            } else if let Some(first_child) = self.entries[handle].first_child {
+                stack.push((handle, Some(index), false));
+                prev = None;
+                current = Some(first_child);
+            } else {
+                prev = Some(index);
+                current = self.entries[handle].next;
+            }
+        }
+
+        (document, self.errors.unwrap_or_default())
+    }
+
+    fn parse_error(&mut self, message: Cow<'static, str>) {
+        if let Some(ref mut errors) = self.errors {
+            let position = self
+                .locator
+                .as_mut()
+                .map(|locator| locator.position(locator.chunk.0));
+            errors.push(Diagnostic { message, position });
+        }
+    }
+
+    fn get_document(&mut self) -> usize {
+        DOCUMENT
+    }
+
+    fn elem_name<'a>(&'a self, target: &'a usize) -> ExpandedName<'a> {
+        match self.entries[*target].data {
+            Some(node::Data::Element(ref name, _)) => name.expanded(),
+            _ => panic!("not an element!"),
+        }
+    }
+
+    fn create_element(
+        &mut self,
+        name: QualName,
+        attrs: Vec<Attribute>,
+        flags: ElementFlags,
+    ) -> usize {
+        let attrs = attrs
+            .into_iter()
+            .map(|attr| (attr.name, to_str(attr.value)))
+            .collect();
+        let template_contents = if flags.template {
+            Some(self.push(Entry::new(None)))
+        } else {
+            None
+        };
+        let local = name.local.clone();
+        let handle = self.push_markup(node::Data::Element(name, attrs), |source| {
+            starts_with_ignore_case(&source[1..], &local)
+                && source[1 + local.len()..]
+                    .starts_with(|c: char| c.is_ascii_whitespace() || c == '/' || c == '>')
+        });
+        let entry = &mut self.entries[handle];
+        entry.integration_point = flags.mathml_annotation_xml_integration_point;
+        entry.template_contents = template_contents;
+        handle
+    }
+
+    fn create_comment(&mut self, text: StrTendril) -> usize {
+        // Bogus comments such as `<?php ... ?>` start with `<?` or `</`.
+        self.push_markup(node::Data::Comment(to_str(text)), |source| {
+            source.starts_with("<!") || source.starts_with("<?") || source.starts_with("</")
+        })
+    }
+
+    fn create_pi(&mut self, target: StrTendril, data: StrTendril) -> usize {
+        self.push_markup(
+            node::Data::ProcessingInstruction(to_str(target), to_str(data)),
+            |source| source.starts_with("<?"),
+        )
+    }
+
+    fn append(&mut self, parent: &usize, child: NodeOrText<usize>) {
+        let child = match child {
+            NodeOrText::AppendNode(node) => node,
+            NodeOrText::AppendText(text) => {
+                if self.append_to_text(self.entries[*parent].last_child, &text) {
+                    return;
+                }
+                self.text(text)
+            }
+        };
+        self.append_child(*parent, child);
+    }
+
+    fn append_based_on_parent_node(
+        &mut self,
+        element: &usize,
+        prev_element: &usize,
+        child: NodeOrText<usize>,
+    ) {
+        if self.entries[*element].parent.is_some() {
+            self.append_before_sibling(element, child);
+        } else {
+            self.append(prev_element, child);
+        }
+    }
+
+    fn append_doctype_to_document(
+        &mut self,
+        name: StrTendril,
+        public_id: StrTendril,
+        system_id: StrTendril,
+    ) {
+        let doctype = self.push_markup(
+            node::Data::Doctype(to_str(name), to_str(public_id), to_str(system_id)),
+            |source| starts_with_ignore_case(source, "<!doctype"),
+        );
+        self.append_child(DOCUMENT, doctype);
+    }
+
+    fn get_template_contents(&mut self, target: &usize) -> usize {
+        self.entries[*target]
+            .template_contents
+            .expect("not a template element!")
+    }
+
+    fn same_node(&self, x: &usize, y: &usize) -> bool {
+        x == y
+    }
+
+    fn set_quirks_mode(&mut self, _: QuirksMode) {}
+
+    fn append_before_sibling(&mut self, sibling: &usize, child: NodeOrText<usize>) {
+        let child = match child {
+            NodeOrText::AppendNode(node) => node,
+            NodeOrText::AppendText(text) => {
+                if self.append_to_text(self.entries[*sibling].prev, &text) {
+                    return;
+                }
+                self.text(text)
+            }
+        };
+        self.insert_before(*sibling, child);
+    }
+
+    fn add_attrs_if_missing(&mut self, target: &usize, attrs: Vec<Attribute>) {
+        if let Some(node::Data::Element(_, ref mut existing)) = self.entries[*target].data {
+            for attr in attrs {
+                if !existing.iter().any(|(name, _)| *name == attr.name) {
+                    existing.push((attr.name, to_str(attr.value)));
+                }
+            }
+        } else {
+            panic!("not an element!")
+        }
+    }
+
+    fn remove_from_parent(&mut self, target: &usize) {
+        self.detach(*target);
+    }
+
+    fn reparent_children(&mut self, node: &usize, new_parent: &usize) {
+        while let Some(child) = self.entries[*node].first_child {
+            self.append_child(*new_parent, child);
+        }
+    }
+
+    fn is_mathml_annotation_xml_integration_point(&self, target: &usize) -> bool {
+        self.entries[*target].integration_point
+    }
+}
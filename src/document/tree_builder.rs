@@ -0,0 +1,145 @@
+use html5ever::{namespace_url, ns, LocalName, QualName};
+
+use super::{append, Document};
+use crate::node::{self, Data};
+
+/// Constructs a `Document` node by node, obtained with `Document::builder`.
+/// The contents of each element are built by an `ElementBuilder`.
+///
+/// Text and attribute values are stored as given and escaped when the
+/// document is serialized.
+///
+/// ```
+/// use select::document::Document;
+///
+/// let document = Document::builder()
+///     .elem("ul", |ul| {
+///         ul.attr("class", "x")
+///             .elem("li", |li| li.text("a & b"))
+///             .elem("li", |li| li.text("c"))
+///     })
+///     .build();
+/// assert_eq!(
+///     document.nth(0).unwrap().html(),
+///     "<ul class=\"x\"><li>a &amp; b</li><li>c</li></ul>"
+/// );
+/// ```
+#[derive(Debug)]
+pub struct TreeBuilder {
+    document: Document,
+    /// The last top-level node.
+    last_root: Option<usize>,
+}
+
+impl TreeBuilder {
+    pub(crate) fn new() -> TreeBuilder {
+        TreeBuilder {
+            document: Document::from(vec![]),
+            last_root: None,
+        }
+    }
+
+    /// Appends an HTML element named `name`, whose attributes and contents
+    /// are built by `f`.
+    pub fn elem<F: FnOnce(ElementBuilder) -> ElementBuilder>(
+        mut self,
+        name: &str,
+        f: F,
+    ) -> TreeBuilder {
+        let index = self.push(element(name));
+        TreeBuilder {
+            document: build(self.document, index, f),
+            ..self
+        }
+    }
+
+    /// Appends a text node.
+    pub fn text(mut self, text: &str) -> TreeBuilder {
+        self.push(Data::Text(node::Str::from(text)));
+        self
+    }
+
+    /// Appends a comment.
+    pub fn comment(mut self, comment: &str) -> TreeBuilder {
+        self.push(Data::Comment(node::Str::from(comment)));
+        self
+    }
+
+    /// Finishes building and returns the `Document`.
+    pub fn build(self) -> Document {
+        self.document
+    }
+
+    fn push(&mut self, data: Data) -> usize {
+        let index = append(&mut self.document, data, None, self.last_root);
+        self.last_root = Some(index);
+        index
+    }
+}
+
+/// Builds the attributes and contents of an element for a `TreeBuilder`.
+#[derive(Debug)]
+pub struct ElementBuilder {
+    document: Document,
+    /// The element being built.
+    element: usize,
+}
+
+impl ElementBuilder {
+    /// Sets an attribute of the element, replacing any existing value.
+    pub fn attr(mut self, name: &str, value: &str) -> ElementBuilder {
+        self.document.set_attr(self.element, name, value);
+        self
+    }
+
+    /// Appends an HTML element named `name`, whose attributes and contents
+    /// are built by `f`.
+    pub fn elem<F: FnOnce(ElementBuilder) -> ElementBuilder>(
+        mut self,
+        name: &str,
+        f: F,
+    ) -> ElementBuilder {
+        let index = self.push(element(name));
+        ElementBuilder {
+            document: build(self.document, index, f),
+            ..self
+        }
+    }
+
+    /// Appends a text node.
+    pub fn text(mut self, text: &str) -> ElementBuilder {
+        self.push(Data::Text(node::Str::from(text)));
+        self
+    }
+
+    /// Appends a comment.
+    pub fn comment(mut self, comment: &str) -> ElementBuilder {
+        self.push(Data::Comment(node::Str::from(comment)));
+        self
+    }
+
+    fn push(&mut self, data: Data) -> usize {
+        let prev = self.document.nodes[self.element].last_child;
+        append(&mut self.document, data, Some(self.element), prev)
+    }
+}
+
+fn element(name: &str) -> Data {
+    Data::Element(
+        QualName::new(None, ns!(html), LocalName::from(name)),
+        vec![],
+    )
+}
+
+/// Builds the element `index` of `document` with `f`.
+fn build<F: FnOnce(ElementBuilder) -> ElementBuilder>(
+    document: Document,
+    index: usize,
+    f: F,
+) -> Document {
+    f(ElementBuilder {
+        document,
+        element: index,
+    })
+    .document
+}
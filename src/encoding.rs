@@ -0,0 +1,241 @@
+//! Character encoding detection, following the HTML specification's encoding
+//! sniffing algorithm.
+//!
+//! Only available with the `encoding` feature.
+
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252, X_USER_DEFINED};
+
+/// The number of bytes inspected by the `<meta>` prescan.
+const PRESCAN_LENGTH: usize = 1024;
+
+/// Determines the character encoding of an HTML byte stream.
+///
+/// In order of precedence, the encoding is taken from a byte order mark, the
+/// `transport` label (e.g. the `charset` parameter of a `Content-Type` header),
+/// a `<meta charset>` or `<meta http-equiv=content-type>` in the first 1024
+/// bytes, and finally falls back to UTF-8 if `bytes` is valid UTF-8 and
+/// windows-1252 otherwise.
+pub fn sniff(bytes: &[u8], transport: Option<&str>) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return encoding;
+    }
+
+    if let Some(encoding) = transport.and_then(|label| Encoding::for_label(label.as_bytes())) {
+        return encoding;
+    }
+
+    if let Some(encoding) = prescan(&bytes[..bytes.len().min(PRESCAN_LENGTH)]) {
+        return encoding;
+    }
+
+    if std::str::from_utf8(bytes).is_ok() {
+        UTF_8
+    } else {
+        WINDOWS_1252
+    }
+}
+
+/// Decodes `bytes` using the encoding determined by `sniff`, replacing
+/// malformed sequences with U+FFFD.
+pub fn decode(bytes: &[u8], transport: Option<&str>) -> (String, &'static Encoding) {
+    let encoding = sniff(bytes, transport);
+    let (string, _) = encoding.decode_with_bom_removal(bytes);
+    (string.into_owned(), encoding)
+}
+
+/// Prescans a byte stream for a `<meta>` element declaring its encoding.
+fn prescan(bytes: &[u8]) -> Option<&'static Encoding> {
+    let mut position = 0;
+
+    while position < bytes.len() {
+        let rest = &bytes[position..];
+
+        if rest.starts_with(b"<!--") {
+            position += 4 + find(&rest[4..], b"-->").map_or(rest.len(), |end| end + 3);
+        } else if starts_with_ignore_case(rest, b"<meta")
+            && rest.get(5).is_some_and(|&b| is_space(b) || b == b'/')
+        {
+            position += 6;
+            if let Some(encoding) = meta(bytes, &mut position) {
+                return Some(encoding);
+            }
+        } else if rest.starts_with(b"</") && rest.get(2).is_some_and(u8::is_ascii_alphabetic)
+            || rest.starts_with(b"<") && rest.get(1).is_some_and(u8::is_ascii_alphabetic)
+        {
+            position += rest
+                .iter()
+                .position(|&b| is_space(b) || b == b'>')
+                .unwrap_or(rest.len());
+            while attribute(bytes, &mut position).is_some() {}
+        } else if rest.starts_with(b"<!") || rest.starts_with(b"</") || rest.starts_with(b"<?") {
+            position += find(rest, b">").map_or(rest.len(), |end| end + 1);
+        } else {
+            position += 1;
+        }
+    }
+
+    None
+}
+
+/// Processes the attributes of a `<meta>` element starting at `position`.
+fn meta(bytes: &[u8], position: &mut usize) -> Option<&'static Encoding> {
+    let mut seen = Vec::new();
+    let mut got_pragma = false;
+    let mut need_pragma = None;
+    let mut charset = None;
+
+    while let Some((name, value)) = attribute(bytes, position) {
+        if seen.contains(&name) {
+            continue;
+        }
+        match &*name {
+            b"http-equiv" => got_pragma |= value.eq_ignore_ascii_case(b"content-type"),
+            b"content" if charset.is_none() => {
+                if let Some(label) = charset_from_content(&value) {
+                    charset = Some(Encoding::for_label(label));
+                    need_pragma = Some(true);
+                }
+            }
+            b"charset" => {
+                charset = Some(Encoding::for_label(&value));
+                need_pragma = Some(false);
+            }
+            _ => {}
+        }
+        seen.push(name);
+    }
+
+    match need_pragma {
+        None => return None,
+        Some(true) if !got_pragma => return None,
+        Some(_) => {}
+    }
+    let encoding = charset.flatten()?;
+
+    Some(if encoding == UTF_16BE || encoding == UTF_16LE {
+        UTF_8
+    } else if encoding == X_USER_DEFINED {
+        WINDOWS_1252
+    } else {
+        encoding
+    })
+}
+
+/// Gets the next attribute of a tag starting at `position`, returning its
+/// lowercased name and value.
+fn attribute(bytes: &[u8], position: &mut usize) -> Option<(Vec<u8>, Vec<u8>)> {
+    let peek = |position: usize| bytes.get(position).copied();
+
+    while peek(*position).is_some_and(|b| is_space(b) || b == b'/') {
+        *position += 1;
+    }
+
+    let mut name = Vec::new();
+    let mut value = Vec::new();
+
+    match peek(*position) {
+        None | Some(b'>') => return None,
+        Some(b) => {
+            name.push(b.to_ascii_lowercase());
+            *position += 1;
+        }
+    }
+
+    loop {
+        match peek(*position)? {
+            b'=' if !name.is_empty() => break,
+            b if is_space(b) => {
+                while peek(*position).is_some_and(is_space) {
+                    *position += 1;
+                }
+                if peek(*position)? != b'=' {
+                    return Some((name, value));
+                }
+                break;
+            }
+            b'/' | b'>' => return Some((name, value)),
+            b => {
+                name.push(b.to_ascii_lowercase());
+                *position += 1;
+            }
+        }
+    }
+
+    // Skip the `=` and any whitespace after it.
+    *position += 1;
+    while peek(*position).is_some_and(is_space) {
+        *position += 1;
+    }
+
+    match peek(*position)? {
+        quote @ (b'"' | b'\'') => {
+            *position += 1;
+            loop {
+                let b = peek(*position)?;
+                *position += 1;
+                if b == quote {
+                    return Some((name, value));
+                }
+                value.push(b.to_ascii_lowercase());
+            }
+        }
+        b'>' => Some((name, value)),
+        _ => {
+            while let Some(b) = peek(*position) {
+                if is_space(b) || b == b'>' {
+                    break;
+                }
+                value.push(b.to_ascii_lowercase());
+                *position += 1;
+            }
+            Some((name, value))
+        }
+    }
+}
+
+/// Extracts the `charset` parameter from a `<meta content>` value.
+fn charset_from_content(content: &[u8]) -> Option<&[u8]> {
+    let mut position = 0;
+    loop {
+        position += find(&content[position..], b"charset")? + 7;
+        while content.get(position).copied().is_some_and(is_space) {
+            position += 1;
+        }
+        if content.get(position) == Some(&b'=') {
+            break;
+        }
+    }
+    position += 1;
+    while content.get(position).copied().is_some_and(is_space) {
+        position += 1;
+    }
+
+    let rest = &content[position..];
+    match rest.first()? {
+        &quote @ (b'"' | b'\'') => {
+            let end = rest[1..].iter().position(|&b| b == quote)?;
+            Some(&rest[1..end + 1])
+        }
+        _ => {
+            let end = rest
+                .iter()
+                .position(|&b| is_space(b) || b == b';')
+                .unwrap_or(rest.len());
+            Some(&rest[..end])
+        }
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn starts_with_ignore_case(bytes: &[u8], prefix: &[u8]) -> bool {
+    bytes.len() >= prefix.len() && bytes[..prefix.len()].eq_ignore_ascii_case(prefix)
+}
+
+fn is_space(b: u8) -> bool {
+    matches!(b, b'\t' | b'\n' | b'\x0C' | b'\r' | b' ')
+}
@@ -1,5 +1,11 @@
 #![warn(missing_debug_implementations)]
 pub mod document;
+#[cfg(feature = "encoding")]
+pub mod encoding;
 pub mod node;
 pub mod predicate;
 pub mod selection;
+pub mod xpath;
+
+#[cfg(feature = "encoding")]
+pub use encoding_rs;
@@ -1,18 +1,47 @@
+use std::collections::VecDeque;
 use std::{fmt, io};
 
-use html5ever::tendril::StrTendril;
-use html5ever::{serialize, QualName};
+use html5ever::{namespace_url, ns, serialize, QualName};
 
 use crate::document::Document;
 use crate::predicate::Predicate;
 use crate::selection::Selection;
 
+/// The string type stored in `Data`. It is `String` rather than the parser's
+/// `StrTendril` so that `Document`, `Node` and `Selection` are `Send` and
+/// `Sync`.
+pub type Str = String;
+
 /// The Node type specific data stored by every Node.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Data {
-    Text(StrTendril),
-    Element(QualName, Vec<(QualName, StrTendril)>),
-    Comment(StrTendril),
+    Text(Str),
+    Element(QualName, Vec<(QualName, Str)>),
+    Comment(Str),
+    /// A document type declaration with a name, public identifier and system
+    /// identifier.
+    Doctype(Str, Str, Str),
+    /// A processing instruction with a target and data.
+    ProcessingInstruction(Str, Str),
+}
+
+/// A position in the source of a Document.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Position {
+    /// The byte offset, starting at 0.
+    pub offset: usize,
+    /// The line number, starting at 1.
+    pub line: usize,
+    /// The column in characters, starting at 1.
+    pub column: usize,
+}
+
+/// The location of a Node in the source of a Document. For elements, this is
+/// the location of the start tag.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
 }
 
 /// Internal representation of a Node. Not of much use without a reference to a
@@ -25,10 +54,14 @@ pub struct Raw {
     pub next: Option<usize>,
     pub first_child: Option<usize>,
     pub last_child: Option<usize>,
+    /// The first top-level node of a `<template>` element's contents. These
+    /// nodes are not children of the template and have no parent.
+    pub template_contents: Option<usize>,
     pub data: Data,
 }
 
-/// A single node of an HTML document. Nodes may be HTML elements, comments, or text nodes.
+/// A single node of an HTML document. Nodes may be HTML elements, comments,
+/// text nodes, doctypes, or processing instructions.
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub struct Node<'a> {
     document: &'a Document,
@@ -50,11 +83,30 @@ impl<'a> Node<'a> {
         self.index
     }
 
+    /// Get the Document this Node belongs to.
+    pub fn document(&self) -> &'a Document {
+        self.document
+    }
+
     /// Obtain the inner representation of this Node.
     pub fn raw(&self) -> &'a Raw {
         &self.document.nodes[self.index]
     }
 
+    /// Get the location of this Node in the source, if the Document was parsed
+    /// with `ParseOptions::spans` enabled and the Node appears in the source.
+    /// Nodes implied by the parser, such as a missing `<body>`, have no span.
+    pub fn span(&self) -> Option<Span> {
+        self.document.spans.get(self.index).copied().flatten()
+    }
+
+    /// Whether this Node has been detached from the Document's tree, either
+    /// directly or along with one of its ancestors. Detached nodes are skipped
+    /// by `Document::find`.
+    pub fn is_detached(&self) -> bool {
+        self.document.is_detached(self.index)
+    }
+
     /// Get the text node, HTML element, or comment from a Node.
     pub fn data(&self) -> &'a Data {
         &self.raw().data
@@ -73,8 +125,8 @@ impl<'a> Node<'a> {
         match *self.data() {
             Data::Element(_, ref attrs) => attrs
                 .iter()
-                .find(|&&(ref name_, _)| name == &name_.local)
-                .map(|&(_, ref value)| value.as_ref()),
+                .find(|(name_, _)| name == &name_.local)
+                .map(|(_, value)| value.as_ref()),
             _ => None,
         }
     }
@@ -120,6 +172,27 @@ impl<'a> Node<'a> {
             .map(|index| self.document.nth(index).unwrap())
     }
 
+    /// Construct an iterator over the top-level Nodes of a `<template>`
+    /// element's contents. Returns an empty iterator for other Nodes.
+    pub fn template_contents(&self) -> Children<'a> {
+        Children {
+            document: self.document,
+            next: self
+                .raw()
+                .template_contents
+                .map(|index| self.document.nth(index).unwrap()),
+        }
+    }
+
+    /// The children of a Node, or the contents of a `<template>` element.
+    fn serialized_children(&self) -> Children<'a> {
+        if self.raw().template_contents.is_some() {
+            self.template_contents()
+        } else {
+            self.children()
+        }
+    }
+
     /// Get the combined textual content of a Node and all of its children.
     pub fn text(&self) -> String {
         let mut string = String::new();
@@ -146,7 +219,7 @@ impl<'a> Node<'a> {
     /// Serialize a Node's children to an HTML string.
     pub fn inner_html(&self) -> String {
         let mut buf = Vec::new();
-        for child in self.children() {
+        for child in self.serialized_children() {
             serialize::serialize(&mut buf, &child, Default::default()).unwrap();
         }
         String::from_utf8(buf).unwrap()
@@ -182,6 +255,23 @@ impl<'a> Node<'a> {
         }
     }
 
+    /// Get the name of a doctype Node, or None if the node is not a doctype.
+    pub fn as_doctype(&self) -> Option<&'a str> {
+        match *self.data() {
+            Data::Doctype(ref name, _, _) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Get the target and data of a processing instruction Node, or None if the
+    /// node is not a processing instruction.
+    pub fn as_processing_instruction(&self) -> Option<(&'a str, &'a str)> {
+        match *self.data() {
+            Data::ProcessingInstruction(ref target, ref data) => Some((target, data)),
+            _ => None,
+        }
+    }
+
     /// Construct an iterator over a Node's child Nodes.
     pub fn children(&self) -> Children<'a> {
         Children {
@@ -190,25 +280,141 @@ impl<'a> Node<'a> {
         }
     }
 
+    /// Construct an iterator over a Node's child Elements, skipping text,
+    /// comments and other non-Element Nodes.
+    pub fn element_children(&self) -> impl Iterator<Item = Node<'a>> {
+        self.children().filter(Node::is_element)
+    }
+
+    /// Get the first child Element of this Node.
+    pub fn first_element_child(&self) -> Option<Node<'a>> {
+        self.element_children().next()
+    }
+
+    /// Get the last child Element of this Node.
+    pub fn last_element_child(&self) -> Option<Node<'a>> {
+        let last = self.last_child()?;
+        std::iter::once(last)
+            .chain(last.preceding_siblings())
+            .find(Node::is_element)
+    }
+
+    /// Get the next sibling Element of this Node.
+    pub fn next_element(&self) -> Option<Node<'a>> {
+        self.following_siblings().find(Node::is_element)
+    }
+
+    /// Get the previous sibling Element of this Node.
+    pub fn prev_element(&self) -> Option<Node<'a>> {
+        self.preceding_siblings().find(Node::is_element)
+    }
+
+    /// Get the position of this Element among its sibling Elements, indexed
+    /// from 0, or `None` if this Node is not an Element.
+    pub fn element_index(&self) -> Option<usize> {
+        if self.is_element() {
+            Some(self.preceding_siblings().filter(Node::is_element).count())
+        } else {
+            None
+        }
+    }
+
+    /// Construct an iterator over the Nodes after this Node with the same
+    /// parent, nearest first.
+    pub fn following_siblings(&self) -> Siblings<'a> {
+        Siblings {
+            document: self.document,
+            next: self.next(),
+            forward: true,
+        }
+    }
+
+    /// Construct an iterator over the Nodes before this Node with the same
+    /// parent, nearest first.
+    pub fn preceding_siblings(&self) -> Siblings<'a> {
+        Siblings {
+            document: self.document,
+            next: self.prev(),
+            forward: false,
+        }
+    }
+
+    /// Construct an iterator over the ancestors of this Node, nearest first.
+    pub fn ancestors(&self) -> Ancestors<'a> {
+        Ancestors {
+            document: self.document,
+            next: self.parent(),
+        }
+    }
+
+    /// Construct an iterator over this Node followed by its ancestors.
+    pub fn ancestors_or_self(&self) -> Ancestors<'a> {
+        Ancestors {
+            document: self.document,
+            next: Some(*self),
+        }
+    }
+
+    /// Get the nearest of this Node and its ancestors matching `p`.
+    pub fn closest<P: Predicate>(&self, p: P) -> Option<Node<'a>> {
+        self.ancestors_or_self().find(|node| p.matches(node))
+    }
+
+    /// Returns whether this Node is an ancestor of `other`.
+    pub fn is_ancestor_of(&self, other: &Node) -> bool {
+        std::ptr::eq(self.document, other.document)
+            && other.ancestors().any(|node| node.index == self.index)
+    }
+
+    /// Get the number of ancestors of this Node. Top-level Nodes have a depth
+    /// of 0.
+    pub fn depth(&self) -> usize {
+        self.ancestors().count()
+    }
+
+    fn is_element(&self) -> bool {
+        matches!(*self.data(), Data::Element(..))
+    }
+
     /// Construct an iterator over a Node's descendant (transitive children) Nodes.
     pub fn descendants(&self) -> Descendants<'a> {
-        Descendants {
+        let mut descendants = Descendants {
+            start: *self,
+            front: None,
+            back: None,
+            templates: false,
+        };
+        descendants.reset();
+        descendants
+    }
+
+    /// Construct an iterator over a Node's descendant Nodes in breadth-first
+    /// order.
+    pub fn descendants_bfs(&self) -> DescendantsBfs<'a> {
+        DescendantsBfs {
+            queue: self.children().collect(),
+        }
+    }
+
+    /// Construct an iterator over the opening and closing edges of this Node
+    /// and its descendants, in document order.
+    pub fn traverse(&self) -> Traverse<'a> {
+        Traverse {
             start: *self,
-            current: *self,
-            done: false,
+            next: Some(Edge::Open(*self)),
         }
     }
 }
 
 impl<'a> fmt::Debug for Node<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        struct Attrs<'a>(&'a [(QualName, StrTendril)]);
+        struct Attrs<'a>(&'a [(QualName, Str)]);
 
         impl<'a> fmt::Debug for Attrs<'a> {
             fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
                 self.0
                     .iter()
-                    .fold(f.debug_list(), |mut f, &(ref name, ref value)| {
+                    .fold(f.debug_list(), |mut f, (name, value)| {
                         f.entry(&(&*name.local, &&**value));
                         f
                     })
@@ -233,10 +439,189 @@ impl<'a> fmt::Debug for Node<'a> {
                 .field("children", &Children(self))
                 .finish(),
             Data::Comment(ref comment) => f.debug_tuple("Comment").field(&&**comment).finish(),
+            Data::Doctype(ref name, ref public_id, ref system_id) => f
+                .debug_struct("Doctype")
+                .field("name", &&**name)
+                .field("public_id", &&**public_id)
+                .field("system_id", &&**system_id)
+                .finish(),
+            Data::ProcessingInstruction(ref target, ref data) => f
+                .debug_struct("ProcessingInstruction")
+                .field("target", &&**target)
+                .field("data", &&**data)
+                .finish(),
         }
     }
 }
 
+/// A mutable handle to a node of a Document, obtained with
+/// `Document::nth_mut`.
+pub struct NodeMut<'a> {
+    document: &'a mut Document,
+    index: usize,
+}
+
+impl<'a> NodeMut<'a> {
+    /// Create a NodeMut referring to the `index`th Node of a document.
+    pub fn new(document: &'a mut Document, index: usize) -> Option<NodeMut<'a>> {
+        if index < document.nodes.len() {
+            Some(NodeMut { document, index })
+        } else {
+            None
+        }
+    }
+
+    /// Get the index of this Node in its Document.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Get a read-only `Node` for this node.
+    pub fn as_node(&self) -> Node<'_> {
+        Node {
+            document: self.document,
+            index: self.index,
+        }
+    }
+
+    /// Set the value of the attribute `name`, replacing any existing value.
+    ///
+    /// # Panics
+    ///
+    /// If this node is not an element.
+    pub fn set_attr(&mut self, name: &str, value: &str) {
+        self.document.set_attr(self.index, name, value);
+    }
+
+    /// Remove the attribute `name`, returning whether it was present.
+    ///
+    /// # Panics
+    ///
+    /// If this node is not an element.
+    pub fn remove_attr(&mut self, name: &str) -> bool {
+        self.document.remove_attr(self.index, name)
+    }
+
+    /// Add `class` to the `class` attribute, unless it is already present.
+    ///
+    /// # Panics
+    ///
+    /// If this node is not an element.
+    pub fn add_class(&mut self, class: &str) {
+        let classes = self.as_node().attr("class").unwrap_or("");
+        if !classes.split_whitespace().any(|name| name == class) {
+            let classes = classes
+                .split_whitespace()
+                .chain(Some(class))
+                .collect::<Vec<_>>()
+                .join(" ");
+            self.set_attr("class", &classes);
+        }
+    }
+
+    /// Remove `class` from the `class` attribute, returning whether it was
+    /// present.
+    ///
+    /// # Panics
+    ///
+    /// If this node is not an element.
+    pub fn remove_class(&mut self, class: &str) -> bool {
+        let classes = match self.as_node().attr("class") {
+            Some(classes) if classes.split_whitespace().any(|name| name == class) => classes,
+            _ => return false,
+        };
+        let classes = classes
+            .split_whitespace()
+            .filter(|&name| name != class)
+            .collect::<Vec<_>>()
+            .join(" ");
+        self.set_attr("class", &classes);
+        true
+    }
+
+    /// Replace the contents of this node with `text`. The children of an
+    /// element are replaced with a single text node.
+    ///
+    /// # Panics
+    ///
+    /// If this node is a doctype or processing instruction.
+    pub fn set_text(&mut self, text: &str) {
+        self.document.index.take();
+        match self.document.nodes[self.index].data {
+            Data::Text(ref mut value) | Data::Comment(ref mut value) => *value = Str::from(text),
+            Data::Element(..) => {
+                self.clear();
+                if !text.is_empty() {
+                    let text = self.document.create_text(text);
+                    self.append(text);
+                }
+            }
+            _ => panic!("cannot set the text of node {}", self.index),
+        }
+    }
+
+    /// Replace the children of this element with `html`, parsed as a fragment
+    /// in the context of this element.
+    ///
+    /// # Panics
+    ///
+    /// If this node is not an element.
+    pub fn set_inner_html(&mut self, html: &str) {
+        let fragment = match self.as_node().name() {
+            Some(name) => Document::from_fragment(html, name),
+            None => panic!("node {} is not an element", self.index),
+        };
+        self.clear();
+        for node in self.document.import(fragment) {
+            self.append(node);
+        }
+    }
+
+    /// Change the local name of this element, keeping its namespace and
+    /// attributes.
+    ///
+    /// # Panics
+    ///
+    /// If this node is not an element.
+    pub fn rename(&mut self, name: &str) {
+        self.document.index.take();
+        match self.document.nodes[self.index].data {
+            Data::Element(ref mut qual_name, _) => qual_name.local = name.into(),
+            _ => panic!("node {} is not an element", self.index),
+        }
+    }
+
+    /// Detach all children of this node, and its template contents.
+    fn clear(&mut self) {
+        while let Some(child) = self.document.nodes[self.index].first_child {
+            self.document.detach(child);
+        }
+        while let Some(content) = self.document.nodes[self.index].template_contents {
+            self.document.detach(content);
+        }
+    }
+
+    /// Append `node` to the children of this element, or to its contents if
+    /// it is a `<template>`.
+    fn append(&mut self, node: usize) {
+        let template = match self.document.nodes[self.index].data {
+            Data::Element(ref name, _) => name.ns == ns!(html) && &*name.local == "template",
+            _ => false,
+        };
+        if template {
+            self.document.append_template_contents(self.index, node);
+        } else {
+            self.document.append_child(self.index, node);
+        }
+    }
+}
+
+impl<'a> fmt::Debug for NodeMut<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        self.as_node().fmt(f)
+    }
+}
+
 impl<'a> serialize::Serialize for Node<'a> {
     fn serialize<S: serialize::Serializer>(
         &self,
@@ -246,11 +631,11 @@ impl<'a> serialize::Serialize for Node<'a> {
         match *self.data() {
             Data::Text(ref text) => serializer.write_text(text),
             Data::Element(ref name, ref attrs) => {
-                let attrs = attrs.iter().map(|&(ref name, ref value)| (name, &**value));
+                let attrs = attrs.iter().map(|(name, value)| (name, &**value));
 
                 serializer.start_elem(name.clone(), attrs)?;
 
-                for child in self.children() {
+                for child in self.serialized_children() {
                     serialize::Serialize::serialize(&child, serializer, traversal_scope.clone())?;
                 }
 
@@ -259,57 +644,206 @@ impl<'a> serialize::Serialize for Node<'a> {
                 Ok(())
             }
             Data::Comment(ref comment) => serializer.write_comment(comment),
+            Data::Doctype(ref name, ref public_id, ref system_id) => {
+                // The serializer only writes the name, so the identifiers are
+                // appended to it.
+                let mut doctype = String::from(&**name);
+                if !public_id.is_empty() {
+                    doctype.push_str(" PUBLIC ");
+                    push_quoted(&mut doctype, public_id);
+                    if !system_id.is_empty() {
+                        doctype.push(' ');
+                        push_quoted(&mut doctype, system_id);
+                    }
+                } else if !system_id.is_empty() {
+                    doctype.push_str(" SYSTEM ");
+                    push_quoted(&mut doctype, system_id);
+                }
+                serializer.write_doctype(&doctype)
+            }
+            Data::ProcessingInstruction(ref target, ref data) => {
+                serializer.write_processing_instruction(target, data)
+            }
         }
     }
 }
 
+/// Appends a doctype identifier, in single quotes if it contains a double
+/// quote.
+fn push_quoted(string: &mut String, id: &str) {
+    let quote = if id.contains('"') { '\'' } else { '"' };
+    string.push(quote);
+    string.push_str(id);
+    string.push(quote);
+}
+
 #[derive(Clone, Debug)]
 pub struct Descendants<'a> {
     start: Node<'a>,
-    current: Node<'a>,
-    done: bool,
+    /// The next Node to yield from the front, if any.
+    front: Option<Node<'a>>,
+    /// The next Node to yield from the back, if any.
+    back: Option<Node<'a>>,
+    templates: bool,
 }
 
-impl<'a> Iterator for Descendants<'a> {
-    type Item = Node<'a>;
+impl<'a> Descendants<'a> {
+    /// Also descend into the contents of `<template>` elements.
+    pub fn include_templates(mut self) -> Descendants<'a> {
+        self.templates = true;
+        self.reset();
+        self
+    }
 
-    fn next(&mut self) -> Option<Node<'a>> {
-        if self.done {
-            return None;
-        }
+    fn reset(&mut self) {
+        self.front = self.first_child(self.start);
+        self.back = self
+            .last_child(self.start)
+            .map(|node| self.last_descendant(node));
+    }
 
-        // If this is the start, we can only descdend into children.
-        if self.start.index() == self.current.index() {
-            if let Some(first_child) = self.current.first_child() {
-                self.current = first_child;
+    fn first_child(&self, node: Node<'a>) -> Option<Node<'a>> {
+        node.first_child().or_else(|| {
+            if self.templates {
+                node.template_contents().next()
             } else {
-                self.done = true;
+                None
+            }
+        })
+    }
+
+    fn last_child(&self, node: Node<'a>) -> Option<Node<'a>> {
+        node.last_child().or_else(|| {
+            if self.templates {
+                node.template_contents().last()
+            } else {
+                None
+            }
+        })
+    }
+
+    /// The last Node of the subtree rooted at `node` in document order.
+    fn last_descendant(&self, mut node: Node<'a>) -> Node<'a> {
+        while let Some(last_child) = self.last_child(node) {
+            node = last_child;
+        }
+        node
+    }
+
+    fn successor(&self, node: Node<'a>) -> Option<Node<'a>> {
+        if let Some(first_child) = self.first_child(node) {
+            return Some(first_child);
+        }
+        let mut node = node;
+        loop {
+            if let Some(next) = node.next() {
+                return Some(next);
+            }
+            node = self.parent(node)?;
+            if node.index() == self.start.index() {
                 return None;
             }
-        } else {
-            // Otherwise we can also go to next sibling.
-            if let Some(first_child) = self.current.first_child() {
-                self.current = first_child;
-            } else if let Some(next) = self.current.next() {
-                self.current = next;
+        }
+    }
+
+    fn predecessor(&self, node: Node<'a>) -> Option<Node<'a>> {
+        match node.prev() {
+            Some(prev) => Some(self.last_descendant(prev)),
+            None => self
+                .parent(node)
+                .filter(|parent| parent.index() != self.start.index()),
+        }
+    }
+
+    /// The parent of `node`, or its template if it is a template's content.
+    fn parent(&self, node: Node<'a>) -> Option<Node<'a>> {
+        node.parent().or_else(|| {
+            if self.templates {
+                let document = node.document();
+                document
+                    .template_of(node.index())
+                    .map(|index| document.nth(index).unwrap())
             } else {
-                loop {
-                    // This unwrap should never fail.
-                    let parent = self.current.parent().unwrap();
-                    if parent.index() == self.start.index() {
-                        self.done = true;
-                        return None;
-                    }
-                    if let Some(next) = parent.next() {
-                        self.current = next;
-                        break;
-                    }
-                    self.current = parent;
-                }
+                None
             }
+        })
+    }
+}
+
+impl<'a> Iterator for Descendants<'a> {
+    type Item = Node<'a>;
+
+    fn next(&mut self) -> Option<Node<'a>> {
+        let node = self.front?;
+        self.front = self.successor(node);
+        // Stop once the two ends meet.
+        if self.back.map(|back| back.index()) == Some(node.index()) {
+            self.front = None;
+            self.back = None;
         }
+        Some(node)
+    }
+}
+
+impl<'a> DoubleEndedIterator for Descendants<'a> {
+    fn next_back(&mut self) -> Option<Node<'a>> {
+        let node = self.back?;
+        self.back = self.predecessor(node);
+        if self.front.map(|front| front.index()) == Some(node.index()) {
+            self.front = None;
+            self.back = None;
+        }
+        Some(node)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DescendantsBfs<'a> {
+    queue: VecDeque<Node<'a>>,
+}
+
+impl<'a> Iterator for DescendantsBfs<'a> {
+    type Item = Node<'a>;
+
+    fn next(&mut self) -> Option<Node<'a>> {
+        let node = self.queue.pop_front()?;
+        self.queue.extend(node.children());
+        Some(node)
+    }
+}
+
+/// An event of `Node::traverse`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Edge<'a> {
+    /// The start of a Node, before its children.
+    Open(Node<'a>),
+    /// The end of a Node, after its children.
+    Close(Node<'a>),
+}
+
+#[derive(Clone, Debug)]
+pub struct Traverse<'a> {
+    start: Node<'a>,
+    next: Option<Edge<'a>>,
+}
 
-        Some(self.current)
+impl<'a> Iterator for Traverse<'a> {
+    type Item = Edge<'a>;
+
+    fn next(&mut self) -> Option<Edge<'a>> {
+        let edge = self.next?;
+        self.next = match edge {
+            Edge::Open(node) => Some(match node.first_child() {
+                Some(first_child) => Edge::Open(first_child),
+                None => Edge::Close(node),
+            }),
+            Edge::Close(node) if node.index() == self.start.index() => None,
+            Edge::Close(node) => match node.next() {
+                Some(next) => Some(Edge::Open(next)),
+                None => node.parent().map(Edge::Close),
+            },
+        };
+        Some(edge)
     }
 }
 
@@ -343,6 +877,55 @@ impl<'a, P: Predicate> Iterator for Find<'a, P> {
     }
 }
 
+#[derive(Clone, Debug)]
+pub struct Ancestors<'a> {
+    document: &'a Document,
+    next: Option<Node<'a>>,
+}
+
+impl<'a> Ancestors<'a> {
+    pub fn into_selection(self) -> Selection<'a> {
+        Selection::new(self.document, self.map(|node| node.index()).collect())
+    }
+}
+
+impl<'a> Iterator for Ancestors<'a> {
+    type Item = Node<'a>;
+
+    fn next(&mut self) -> Option<Node<'a>> {
+        let next = self.next?;
+        self.next = next.parent();
+        Some(next)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Siblings<'a> {
+    document: &'a Document,
+    next: Option<Node<'a>>,
+    forward: bool,
+}
+
+impl<'a> Siblings<'a> {
+    pub fn into_selection(self) -> Selection<'a> {
+        Selection::new(self.document, self.map(|node| node.index()).collect())
+    }
+}
+
+impl<'a> Iterator for Siblings<'a> {
+    type Item = Node<'a>;
+
+    fn next(&mut self) -> Option<Node<'a>> {
+        let next = self.next?;
+        self.next = if self.forward {
+            next.next()
+        } else {
+            next.prev()
+        };
+        Some(next)
+    }
+}
+
 #[derive(Debug)]
 pub struct Children<'a> {
     document: &'a Document,
@@ -1,8 +1,17 @@
 use crate::node::{self, Node};
 
+pub mod css;
+
+pub use self::css::Css;
+
 /// A trait implemented by all `Node` matchers.
 pub trait Predicate {
     fn matches(&self, node: &Node) -> bool;
+    /// Describes what every matching Node has, so queries can skip Nodes
+    /// which cannot match. The default knows nothing.
+    fn hint(&self) -> PredicateHint<'_> {
+        PredicateHint::default()
+    }
     fn or<T: Predicate>(self, other: T) -> Or<Self, T>
     where
         Self: Sized,
@@ -35,6 +44,72 @@ pub trait Predicate {
     }
 }
 
+/// What every Node matching a Predicate has, as returned by
+/// `Predicate::hint`. Every part is a necessary condition; an empty hint means
+/// nothing is known.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PredicateHint<'a> {
+    /// The local name of the Element.
+    pub name: Option<&'a str>,
+    /// The value of the `id` attribute.
+    pub id: Option<&'a str>,
+    /// Classes the Element has.
+    pub classes: Vec<&'a str>,
+    /// Names of attributes the Element has.
+    pub attrs: Vec<&'a str>,
+    /// If not empty, the Node also satisfies at least one of these hints.
+    pub any_of: Vec<PredicateHint<'a>>,
+    /// Hints each satisfied by some ancestor of the Node.
+    pub ancestors: Vec<PredicateHint<'a>>,
+}
+
+impl<'a> PredicateHint<'a> {
+    /// Returns whether nothing is known.
+    pub fn is_empty(&self) -> bool {
+        *self == PredicateHint::default()
+    }
+
+    /// Combines hints which both hold.
+    pub fn and(mut self, other: PredicateHint<'a>) -> PredicateHint<'a> {
+        self.name = self.name.or(other.name);
+        self.id = self.id.or(other.id);
+        self.classes.extend(other.classes);
+        self.attrs.extend(other.attrs);
+        // Only one set of alternatives is kept, which is still a necessary
+        // condition.
+        if self.any_of.is_empty() {
+            self.any_of = other.any_of;
+        }
+        self.ancestors.extend(other.ancestors);
+        self
+    }
+
+    /// Combines hints of which at least one holds.
+    pub fn or(self, other: PredicateHint<'a>) -> PredicateHint<'a> {
+        if self.is_empty() || other.is_empty() {
+            return PredicateHint::default();
+        }
+        let alternatives = |hint: PredicateHint<'a>| {
+            let only_alternatives = PredicateHint {
+                any_of: vec![],
+                ..hint.clone()
+            }
+            .is_empty();
+            if only_alternatives {
+                hint.any_of
+            } else {
+                vec![hint]
+            }
+        };
+        let mut any_of = alternatives(self);
+        any_of.extend(alternatives(other));
+        PredicateHint {
+            any_of,
+            ..Default::default()
+        }
+    }
+}
+
 /// Matches any Node.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct Any;
@@ -49,21 +124,92 @@ impl Predicate for Any {
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct Name<T>(pub T);
 
-impl<'a> Predicate for Name<&'a str> {
+impl Predicate for Name<&str> {
     fn matches(&self, node: &Node) -> bool {
         node.name() == Some(self.0)
     }
+
+    fn hint(&self) -> PredicateHint<'_> {
+        PredicateHint {
+            name: Some(self.0),
+            ..Default::default()
+        }
+    }
+}
+
+impl Predicate for Name<String> {
+    fn matches(&self, node: &Node) -> bool {
+        Name(&*self.0).matches(node)
+    }
+
+    fn hint(&self) -> PredicateHint<'_> {
+        PredicateHint {
+            name: Some(&self.0),
+            ..Default::default()
+        }
+    }
 }
 
 /// Matches Element Node containing class `T`.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct Class<T>(pub T);
 
-impl<'a> Predicate for Class<&'a str> {
+impl Predicate for Class<&str> {
+    fn matches(&self, node: &Node) -> bool {
+        node.attr("class")
+            .is_some_and(|classes| classes.split_whitespace().any(|class| class == self.0))
+    }
+
+    fn hint(&self) -> PredicateHint<'_> {
+        PredicateHint {
+            classes: vec![self.0],
+            ..Default::default()
+        }
+    }
+}
+
+impl Predicate for Class<String> {
+    fn matches(&self, node: &Node) -> bool {
+        Class(&*self.0).matches(node)
+    }
+
+    fn hint(&self) -> PredicateHint<'_> {
+        PredicateHint {
+            classes: vec![&self.0],
+            ..Default::default()
+        }
+    }
+}
+
+/// Matches Element Node with id `T`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Id<T>(pub T);
+
+impl Predicate for Id<&str> {
+    fn matches(&self, node: &Node) -> bool {
+        node.attr("id") == Some(self.0)
+    }
+
+    fn hint(&self) -> PredicateHint<'_> {
+        PredicateHint {
+            id: Some(self.0),
+            attrs: vec!["id"],
+            ..Default::default()
+        }
+    }
+}
+
+impl Predicate for Id<String> {
     fn matches(&self, node: &Node) -> bool {
-        node.attr("class").map_or(false, |classes| {
-            classes.split_whitespace().any(|class| class == self.0)
-        })
+        Id(&*self.0).matches(node)
+    }
+
+    fn hint(&self) -> PredicateHint<'_> {
+        PredicateHint {
+            id: Some(&self.0),
+            attrs: vec!["id"],
+            ..Default::default()
+        }
     }
 }
 
@@ -77,20 +223,180 @@ impl<T: Predicate> Predicate for Not<T> {
     }
 }
 
-/// Matches Element Node containing attribute `N` with value `V` if `V` is an
-/// `&str`, or any value if `V` is `()`.
+/// Matches Element Node containing attribute `N` whose value satisfies `V`:
+/// equals it if `V` is an `&str` or `String`, any value if `V` is `()`, or one
+/// of the `AttrValue` conditions such as `StartsWith`.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct Attr<N, V>(pub N, pub V);
 
-impl<'a> Predicate for Attr<&'a str, &'a str> {
+impl<V: AttrValue> Predicate for Attr<&str, V> {
     fn matches(&self, node: &Node) -> bool {
-        node.attr(self.0) == Some(self.1)
+        node.attr(self.0)
+            .is_some_and(|value| self.1.matches(value, false))
+    }
+
+    fn hint(&self) -> PredicateHint<'_> {
+        PredicateHint {
+            id: self.1.exact().filter(|_| self.0 == "id"),
+            attrs: vec![self.0],
+            ..Default::default()
+        }
     }
 }
 
-impl<'a> Predicate for Attr<&'a str, ()> {
+impl<V: AttrValue> Predicate for Attr<String, V> {
     fn matches(&self, node: &Node) -> bool {
-        node.attr(self.0).is_some()
+        node.attr(&self.0)
+            .is_some_and(|value| self.1.matches(value, false))
+    }
+
+    fn hint(&self) -> PredicateHint<'_> {
+        PredicateHint {
+            id: self.1.exact().filter(|_| self.0 == "id"),
+            attrs: vec![&self.0],
+            ..Default::default()
+        }
+    }
+}
+
+/// A condition on the value of an attribute, checked by `Attr`. The
+/// conditions follow the CSS attribute selectors.
+pub trait AttrValue {
+    /// Returns whether `value` satisfies the condition, comparing ASCII
+    /// letters case-insensitively if `ignore_case` is true.
+    fn matches(&self, value: &str, ignore_case: bool) -> bool;
+    /// The only value satisfying the condition, if there is one.
+    fn exact(&self) -> Option<&str> {
+        None
+    }
+}
+
+/// Satisfied by any value, as `[name]` in CSS.
+impl AttrValue for () {
+    fn matches(&self, _: &str, _: bool) -> bool {
+        true
+    }
+}
+
+/// Satisfied by an equal value, as `[name=value]` in CSS.
+impl AttrValue for &str {
+    fn matches(&self, value: &str, ignore_case: bool) -> bool {
+        eq(value, self, ignore_case)
+    }
+
+    fn exact(&self) -> Option<&str> {
+        Some(self)
+    }
+}
+
+/// Satisfied by an equal value, as `[name=value]` in CSS.
+impl AttrValue for String {
+    fn matches(&self, value: &str, ignore_case: bool) -> bool {
+        eq(value, self, ignore_case)
+    }
+
+    fn exact(&self) -> Option<&str> {
+        Some(self)
+    }
+}
+
+/// Satisfied by a value starting with `T`, unless `T` is empty, as `^=` in CSS.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct StartsWith<T>(pub T);
+
+impl<T: AsRef<str>> AttrValue for StartsWith<T> {
+    fn matches(&self, value: &str, ignore_case: bool) -> bool {
+        let prefix = self.0.as_ref();
+        !prefix.is_empty()
+            && value.len() >= prefix.len()
+            && eq_bytes(&value.as_bytes()[..prefix.len()], prefix, ignore_case)
+    }
+}
+
+/// Satisfied by a value ending with `T`, unless `T` is empty, as `$=` in CSS.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct EndsWith<T>(pub T);
+
+impl<T: AsRef<str>> AttrValue for EndsWith<T> {
+    fn matches(&self, value: &str, ignore_case: bool) -> bool {
+        let suffix = self.0.as_ref();
+        !suffix.is_empty()
+            && value.len() >= suffix.len()
+            && eq_bytes(
+                &value.as_bytes()[value.len() - suffix.len()..],
+                suffix,
+                ignore_case,
+            )
+    }
+}
+
+/// Satisfied by a value containing `T`, unless `T` is empty, as `*=` in CSS.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Contains<T>(pub T);
+
+impl<T: AsRef<str>> AttrValue for Contains<T> {
+    fn matches(&self, value: &str, ignore_case: bool) -> bool {
+        let needle = self.0.as_ref();
+        !needle.is_empty()
+            && value
+                .as_bytes()
+                .windows(needle.len())
+                .any(|window| eq_bytes(window, needle, ignore_case))
+    }
+}
+
+/// Satisfied by a whitespace-separated list of words, one of which is `T`, as
+/// `~=` in CSS. Never satisfied if `T` is empty or contains whitespace.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Token<T>(pub T);
+
+impl<T: AsRef<str>> AttrValue for Token<T> {
+    fn matches(&self, value: &str, ignore_case: bool) -> bool {
+        let token = self.0.as_ref();
+        !token.is_empty()
+            && !token.contains(char::is_whitespace)
+            && value
+                .split_whitespace()
+                .any(|word| eq(word, token, ignore_case))
+    }
+}
+
+/// Satisfied by `T` or a value starting with `T` followed by `-`, such as
+/// `en-US` for `en`, as `|=` in CSS.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DashPrefix<T>(pub T);
+
+impl<T: AsRef<str>> AttrValue for DashPrefix<T> {
+    fn matches(&self, value: &str, ignore_case: bool) -> bool {
+        let prefix = self.0.as_ref();
+        eq(value, prefix, ignore_case)
+            || value.len() > prefix.len()
+                && value.as_bytes()[prefix.len()] == b'-'
+                && eq_bytes(&value.as_bytes()[..prefix.len()], prefix, ignore_case)
+    }
+}
+
+/// Satisfied if `T` is, comparing ASCII letters case-insensitively, as the `i`
+/// flag in CSS. For example, `Attr("type", IgnoreCase("text"))` matches
+/// `type="TEXT"`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct IgnoreCase<T>(pub T);
+
+impl<T: AttrValue> AttrValue for IgnoreCase<T> {
+    fn matches(&self, value: &str, _: bool) -> bool {
+        self.0.matches(value, true)
+    }
+}
+
+fn eq(a: &str, b: &str, ignore_case: bool) -> bool {
+    eq_bytes(a.as_bytes(), b, ignore_case)
+}
+
+fn eq_bytes(a: &[u8], b: &str, ignore_case: bool) -> bool {
+    if ignore_case {
+        a.eq_ignore_ascii_case(b.as_bytes())
+    } else {
+        a == b.as_bytes()
     }
 }
 
@@ -101,6 +407,17 @@ impl<F: Fn(&Node) -> bool> Predicate for F {
     }
 }
 
+/// Matches if the boxed Predicate matches.
+impl Predicate for Box<dyn Predicate> {
+    fn matches(&self, node: &Node) -> bool {
+        (**self).matches(node)
+    }
+
+    fn hint(&self) -> PredicateHint<'_> {
+        (**self).hint()
+    }
+}
+
 /// Matches any Element Node.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct Element;
@@ -131,6 +448,26 @@ impl Predicate for Comment {
     }
 }
 
+/// Matches any Doctype Node.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Doctype;
+
+impl Predicate for Doctype {
+    fn matches(&self, node: &Node) -> bool {
+        matches!(*node.data(), node::Data::Doctype(..))
+    }
+}
+
+/// Matches any Processing Instruction Node.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ProcessingInstruction;
+
+impl Predicate for ProcessingInstruction {
+    fn matches(&self, node: &Node) -> bool {
+        matches!(*node.data(), node::Data::ProcessingInstruction(..))
+    }
+}
+
 /// Matches if either inner Predicate `A` or `B` matches the Node.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct Or<A, B>(pub A, pub B);
@@ -139,6 +476,10 @@ impl<A: Predicate, B: Predicate> Predicate for Or<A, B> {
     fn matches(&self, node: &Node) -> bool {
         self.0.matches(node) || self.1.matches(node)
     }
+
+    fn hint(&self) -> PredicateHint<'_> {
+        self.0.hint().or(self.1.hint())
+    }
 }
 
 /// Matches if the inner Predicate `A` and `B` both match the Node.
@@ -149,6 +490,10 @@ impl<A: Predicate, B: Predicate> Predicate for And<A, B> {
     fn matches(&self, node: &Node) -> bool {
         self.0.matches(node) && self.1.matches(node)
     }
+
+    fn hint(&self) -> PredicateHint<'_> {
+        self.0.hint().and(self.1.hint())
+    }
 }
 
 /// Matches if inner Predicate `B` matches the node and `A` matches the parent
@@ -164,6 +509,12 @@ impl<A: Predicate, B: Predicate> Predicate for Child<A, B> {
             false
         }
     }
+
+    fn hint(&self) -> PredicateHint<'_> {
+        let mut hint = self.1.hint();
+        hint.ancestors.push(self.0.hint());
+        hint
+    }
 }
 
 /// Matches if inner Predicate `B` matches the node and `A` matches any of the
@@ -173,15 +524,12 @@ pub struct Descendant<A, B>(pub A, pub B);
 
 impl<A: Predicate, B: Predicate> Predicate for Descendant<A, B> {
     fn matches(&self, node: &Node) -> bool {
-        if self.1.matches(node) {
-            let mut node = *node;
-            while let Some(parent) = node.parent() {
-                if self.0.matches(&parent) {
-                    return true;
-                }
-                node = parent;
-            }
-        }
-        false
+        self.1.matches(node) && node.ancestors().any(|ancestor| self.0.matches(&ancestor))
+    }
+
+    fn hint(&self) -> PredicateHint<'_> {
+        let mut hint = self.1.hint();
+        hint.ancestors.push(self.0.hint());
+        hint
     }
 }
@@ -0,0 +1,580 @@
+//! Compiles CSS selector strings into Predicates.
+//!
+//! Supported syntax:
+//!
+//! * Selector lists: `a, b`.
+//! * Type, universal, id and class selectors: `div`, `*`, `#main`, `.post`.
+//! * Attribute selectors: `[href]`, `[a=b]`, `[a~=b]`, `[a|=b]`, `[a^=b]`,
+//!   `[a$=b]` and `[a*=b]`, optionally with the `i` or `s` flag.
+//! * All four combinators: ` `, `>`, `+` and `~`.
+//! * Pseudo-classes: `:root`, `:empty`, `:first-child`, `:last-child`,
+//!   `:only-child`, `:first-of-type`, `:last-of-type`, `:only-of-type`,
+//!   `:nth-child()`, `:nth-last-child()`, `:nth-of-type()`,
+//!   `:nth-last-of-type()`, `:not()`, `:is()` and `:where()`.
+
+use std::{error, fmt, str};
+
+use crate::node::{self, Node};
+use crate::predicate::{
+    And, Attr, AttrValue, Child, Class, Contains, DashPrefix, Descendant, Element, EndsWith, Id,
+    IgnoreCase, Name, Not, Or, Predicate, PredicateHint, StartsWith, Token,
+};
+
+/// Matches if the compiled CSS selector matches the Node.
+pub struct Css {
+    source: String,
+    predicate: Box<dyn Predicate>,
+}
+
+impl Css {
+    /// Parses and compiles a CSS selector list.
+    pub fn parse(source: &str) -> Result<Css, ParseError> {
+        let mut parser = Parser {
+            input: source,
+            position: 0,
+        };
+        parser.skip_whitespace();
+        let predicate = parser.selector_list()?;
+        if parser.position < source.len() {
+            return parser.error("unexpected character");
+        }
+        Ok(Css {
+            source: source.into(),
+            predicate,
+        })
+    }
+
+    /// Get the source string this selector was parsed from.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Convert into the compiled Predicate.
+    pub fn into_predicate(self) -> Box<dyn Predicate> {
+        self.predicate
+    }
+}
+
+impl Predicate for Css {
+    fn matches(&self, node: &Node) -> bool {
+        self.predicate.matches(node)
+    }
+
+    fn hint(&self) -> PredicateHint<'_> {
+        self.predicate.hint()
+    }
+}
+
+impl fmt::Debug for Css {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Css").field(&self.source).finish()
+    }
+}
+
+impl str::FromStr for Css {
+    type Err = ParseError;
+
+    fn from_str(source: &str) -> Result<Css, ParseError> {
+        Css::parse(source)
+    }
+}
+
+/// An error encountered while parsing a CSS selector.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// The byte offset in the source string at which the error occurred.
+    pub position: usize,
+    pub message: &'static str,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl error::Error for ParseError {}
+
+type Result<T, E = ParseError> = std::result::Result<T, E>;
+
+struct Parser<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error<T>(&self, message: &'static str) -> Result<T> {
+        Err(ParseError {
+            position: self.position,
+            message,
+        })
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.position..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += c.len_utf8();
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.position += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char, message: &'static str) -> Result<()> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            self.error(message)
+        }
+    }
+
+    /// Skips whitespace and returns whether any was skipped.
+    fn skip_whitespace(&mut self) -> bool {
+        let start = self.position;
+        while self.peek().is_some_and(is_whitespace) {
+            self.position += 1;
+        }
+        self.position > start
+    }
+
+    fn selector_list(&mut self) -> Result<Box<dyn Predicate>> {
+        let mut predicate = self.complex()?;
+        while self.eat(',') {
+            self.skip_whitespace();
+            predicate = Box::new(Or(predicate, self.complex()?));
+        }
+        Ok(predicate)
+    }
+
+    fn complex(&mut self) -> Result<Box<dyn Predicate>> {
+        let mut predicate = self.compound()?;
+        loop {
+            let whitespace = self.skip_whitespace();
+            let combinator = match self.peek() {
+                Some(c @ ('>' | '+' | '~')) => {
+                    self.bump();
+                    self.skip_whitespace();
+                    c
+                }
+                Some(c) if whitespace && starts_compound(c) => ' ',
+                _ => return Ok(predicate),
+            };
+            let right = self.compound()?;
+            predicate = match combinator {
+                ' ' => Box::new(Descendant(predicate, right)),
+                '>' => Box::new(Child(predicate, right)),
+                '+' => Box::new(Adjacent(predicate, right)),
+                _ => Box::new(Sibling(predicate, right)),
+            };
+        }
+    }
+
+    fn compound(&mut self) -> Result<Box<dyn Predicate>> {
+        let mut predicate: Box<dyn Predicate> = match self.peek() {
+            Some('*') => {
+                self.bump();
+                Box::new(Element)
+            }
+            Some(c) if starts_ident(c) || c == '-' => {
+                Box::new(Name(self.ident()?.to_ascii_lowercase()))
+            }
+            Some('#' | '.' | '[' | ':') => Box::new(Element),
+            _ => return self.error("expected selector"),
+        };
+        loop {
+            let simple: Box<dyn Predicate> = match self.peek() {
+                Some('#') => {
+                    self.bump();
+                    Box::new(Id(self.ident()?))
+                }
+                Some('.') => {
+                    self.bump();
+                    Box::new(Class(self.ident()?))
+                }
+                Some('[') => self.attribute()?,
+                Some(':') => self.pseudo_class()?,
+                _ => return Ok(predicate),
+            };
+            predicate = Box::new(And(predicate, simple));
+        }
+    }
+
+    fn attribute(&mut self) -> Result<Box<dyn Predicate>> {
+        self.expect('[', "expected '['")?;
+        self.skip_whitespace();
+        let name = self.ident()?.to_ascii_lowercase();
+        self.skip_whitespace();
+        if self.eat(']') {
+            return Ok(Box::new(Attr(name, ())));
+        }
+        let start = self.position;
+        let operator = match self.bump() {
+            Some('=') => '=',
+            Some(c @ ('~' | '|' | '^' | '$' | '*')) if self.eat('=') => c,
+            _ => {
+                self.position = start;
+                return self.error("expected attribute operator or ']'");
+            }
+        };
+        self.skip_whitespace();
+        let value = match self.peek() {
+            Some('"' | '\'') => self.string()?,
+            _ => self.ident()?,
+        };
+        self.skip_whitespace();
+        let ignore_case = match self.peek() {
+            Some('i' | 'I') => true,
+            Some('s' | 'S') => false,
+            _ => {
+                self.expect(']', "expected ']'")?;
+                return Ok(attr(name, operator, value, false));
+            }
+        };
+        self.bump();
+        self.skip_whitespace();
+        self.expect(']', "expected ']'")?;
+        Ok(attr(name, operator, value, ignore_case))
+    }
+
+    fn pseudo_class(&mut self) -> Result<Box<dyn Predicate>> {
+        self.expect(':', "expected ':'")?;
+        if self.peek() == Some(':') {
+            return self.error("pseudo-elements are not supported");
+        }
+        let start = self.position;
+        let name = self.ident()?.to_ascii_lowercase();
+        if !self.eat('(') {
+            let (from_end, of_type) = match &*name {
+                "root" => return Ok(Box::new(Root)),
+                "empty" => return Ok(Box::new(Empty)),
+                "first-child" => (false, false),
+                "last-child" => (true, false),
+                "first-of-type" => (false, true),
+                "last-of-type" => (true, true),
+                "only-child" => {
+                    return Ok(Box::new(And(
+                        nth(0, 1, false, false),
+                        nth(0, 1, true, false),
+                    )))
+                }
+                "only-of-type" => {
+                    return Ok(Box::new(And(nth(0, 1, false, true), nth(0, 1, true, true))))
+                }
+                _ => {
+                    self.position = start;
+                    return self.error("unsupported pseudo-class");
+                }
+            };
+            return Ok(Box::new(nth(0, 1, from_end, of_type)));
+        }
+        self.skip_whitespace();
+        let predicate: Box<dyn Predicate> = match &*name {
+            "not" => Box::new(Not(self.selector_list()?)),
+            "is" | "where" => self.selector_list()?,
+            "nth-child" | "nth-last-child" | "nth-of-type" | "nth-last-of-type" => {
+                let (a, b) = self.an_plus_b()?;
+                Box::new(nth(a, b, name.contains("last"), name.ends_with("of-type")))
+            }
+            _ => {
+                self.position = start;
+                return self.error("unsupported pseudo-class");
+            }
+        };
+        self.skip_whitespace();
+        self.expect(')', "expected ')'")?;
+        Ok(predicate)
+    }
+
+    /// Parses the `An+B` microsyntax, including `odd` and `even`.
+    fn an_plus_b(&mut self) -> Result<(i64, i64)> {
+        let start = self.position;
+        let rest = &self.input[start..];
+        let end = rest
+            .find(|c| c == ')' || is_whitespace(c))
+            .unwrap_or(rest.len());
+        let mut word = rest[..end].to_ascii_lowercase();
+        // Allow whitespace around the sign of B, e.g. `2n + 1` or `2n- 1`.
+        let after = rest[end..].trim_start_matches(is_whitespace);
+        let signed = if word.ends_with('n') {
+            match after.chars().next() {
+                Some(sign @ ('+' | '-')) => Some((Some(sign), &after[1..])),
+                _ => None,
+            }
+        } else if word.ends_with("n+") || word.ends_with("n-") {
+            Some((None, after))
+        } else {
+            None
+        };
+        if let Some((sign, digits)) = signed {
+            let digits = digits.trim_start_matches(is_whitespace);
+            let len = digits
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(digits.len());
+            if len > 0 {
+                word.extend(sign);
+                word.push_str(&digits[..len]);
+                self.position = self.input.len() - (digits.len() - len);
+            }
+        }
+        if self.position == start {
+            self.position += end;
+        }
+        let parsed = match &*word {
+            "odd" => Some((2, 1)),
+            "even" => Some((2, 0)),
+            _ => match word.find('n') {
+                Some(n) => {
+                    let a = match &word[..n] {
+                        "" | "+" => Some(1),
+                        "-" => Some(-1),
+                        a => a.parse().ok(),
+                    };
+                    let b = match &word[n + 1..] {
+                        "" => Some(0),
+                        b if b.starts_with(['+', '-']) => b.parse().ok(),
+                        _ => None,
+                    };
+                    a.zip(b)
+                }
+                None => word.parse().ok().map(|b| (0, b)),
+            },
+        };
+        match parsed {
+            Some(parsed) => Ok(parsed),
+            None => {
+                self.position = start;
+                self.error("invalid An+B expression")
+            }
+        }
+    }
+
+    fn ident(&mut self) -> Result<String> {
+        let start = self.position;
+        let mut ident = String::new();
+        if self.eat('-') {
+            ident.push('-');
+        }
+        match self.peek() {
+            Some(c) if starts_ident(c) || c == '-' => {}
+            _ => {
+                self.position = start;
+                return self.error("expected identifier");
+            }
+        }
+        loop {
+            match self.peek() {
+                Some('\\') => ident.push(self.escape()?),
+                Some(c) if c.is_ascii_alphanumeric() || c == '-' || c == '_' || !c.is_ascii() => {
+                    self.bump();
+                    ident.push(c);
+                }
+                _ => return Ok(ident),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let start = self.position;
+        let quote = self.bump();
+        let mut string = String::new();
+        loop {
+            match self.peek() {
+                Some('\\') => {
+                    // An escaped newline is a line continuation.
+                    if self.input[self.position + 1..].starts_with('\n') {
+                        self.position += 2;
+                    } else {
+                        string.push(self.escape()?);
+                    }
+                }
+                Some('\n') => return self.error("newline in string"),
+                Some(c) => {
+                    self.bump();
+                    if Some(c) == quote {
+                        return Ok(string);
+                    }
+                    string.push(c);
+                }
+                None => {
+                    self.position = start;
+                    return self.error("unterminated string");
+                }
+            }
+        }
+    }
+
+    fn escape(&mut self) -> Result<char> {
+        self.expect('\\', "expected '\\'")?;
+        let rest = &self.input[self.position..];
+        let len = rest
+            .find(|c: char| !c.is_ascii_hexdigit())
+            .unwrap_or(rest.len())
+            .min(6);
+        if len == 0 {
+            return match self.bump() {
+                Some(c) => Ok(c),
+                None => self.error("unexpected end of input in escape"),
+            };
+        }
+        let code = u32::from_str_radix(&rest[..len], 16).unwrap();
+        self.position += len;
+        // A single whitespace character terminates a hex escape.
+        self.skip_one_whitespace();
+        match char::from_u32(code) {
+            Some(c) if code != 0 => Ok(c),
+            _ => Ok('\u{FFFD}'),
+        }
+    }
+
+    fn skip_one_whitespace(&mut self) {
+        if self.peek().is_some_and(is_whitespace) {
+            self.position += 1;
+        }
+    }
+}
+
+/// Returns whether `c` is whitespace in CSS.
+fn is_whitespace(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\n' | '\r' | '\x0C')
+}
+
+fn starts_ident(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || c == '\\' || !c.is_ascii()
+}
+
+fn starts_compound(c: char) -> bool {
+    starts_ident(c) || matches!(c, '-' | '*' | '#' | '.' | '[' | ':')
+}
+
+/// Returns the preceding sibling Elements of a Node, nearest first.
+fn prev_elements<'a>(node: &Node<'a>) -> impl Iterator<Item = Node<'a>> {
+    node.preceding_siblings()
+        .filter(|node| node.name().is_some())
+}
+
+/// Matches if `B` matches the Node and `A` matches the immediately preceding
+/// sibling Element.
+struct Adjacent<A, B>(A, B);
+
+impl<A: Predicate, B: Predicate> Predicate for Adjacent<A, B> {
+    fn matches(&self, node: &Node) -> bool {
+        self.1.matches(node)
+            && prev_elements(node)
+                .next()
+                .is_some_and(|prev| self.0.matches(&prev))
+    }
+}
+
+/// Matches if `B` matches the Node and `A` matches any preceding sibling
+/// Element.
+struct Sibling<A, B>(A, B);
+
+impl<A: Predicate, B: Predicate> Predicate for Sibling<A, B> {
+    fn matches(&self, node: &Node) -> bool {
+        self.1.matches(node) && prev_elements(node).any(|prev| self.0.matches(&prev))
+    }
+}
+
+/// Builds the predicate of the attribute selector `[name <operator>= value]`.
+fn attr(name: String, operator: char, value: String, ignore_case: bool) -> Box<dyn Predicate> {
+    fn boxed<V: AttrValue + 'static>(
+        name: String,
+        value: V,
+        ignore_case: bool,
+    ) -> Box<dyn Predicate> {
+        if ignore_case {
+            Box::new(Attr(name, IgnoreCase(value)))
+        } else {
+            Box::new(Attr(name, value))
+        }
+    }
+    match operator {
+        '=' => boxed(name, value, ignore_case),
+        '~' => boxed(name, Token(value), ignore_case),
+        '|' => boxed(name, DashPrefix(value), ignore_case),
+        '^' => boxed(name, StartsWith(value), ignore_case),
+        '$' => boxed(name, EndsWith(value), ignore_case),
+        _ => boxed(name, Contains(value), ignore_case),
+    }
+}
+
+/// Matches the root Element of the document.
+struct Root;
+
+impl Predicate for Root {
+    fn matches(&self, node: &Node) -> bool {
+        // Template contents have no parent either.
+        node.name().is_some()
+            && node.parent().is_none()
+            && node.document().template_of(node.index()).is_none()
+    }
+}
+
+/// Matches Elements with no children other than comments and processing
+/// instructions.
+struct Empty;
+
+impl Predicate for Empty {
+    fn matches(&self, node: &Node) -> bool {
+        node.name().is_some()
+            && node.children().all(|child| {
+                matches!(
+                    *child.data(),
+                    node::Data::Comment(..) | node::Data::ProcessingInstruction(..)
+                )
+            })
+    }
+}
+
+fn nth(a: i64, b: i64, from_end: bool, of_type: bool) -> Nth {
+    Nth {
+        a,
+        b,
+        from_end,
+        of_type,
+    }
+}
+
+/// Matches Elements whose 1-based position among their sibling Elements (of
+/// the same name if `of_type`) is `a * n + b` for some `n >= 0`.
+struct Nth {
+    a: i64,
+    b: i64,
+    from_end: bool,
+    of_type: bool,
+}
+
+impl Predicate for Nth {
+    fn matches<'a>(&self, node: &Node<'a>) -> bool {
+        let name = match node.name() {
+            Some(name) => name,
+            None => return false,
+        };
+        let siblings = if self.from_end {
+            node.following_siblings()
+        } else {
+            node.preceding_siblings()
+        };
+        let position = 1 + siblings
+            .filter(|sibling| match sibling.name() {
+                Some(sibling) => !self.of_type || sibling == name,
+                None => false,
+            })
+            .count() as i64;
+        if self.a == 0 {
+            position == self.b
+        } else {
+            let n = position - self.b;
+            n % self.a == 0 && n / self.a >= 0
+        }
+    }
+}
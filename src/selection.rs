@@ -1,7 +1,8 @@
 use crate::document::Document;
 use crate::node::Node;
-use crate::predicate::Predicate;
+use crate::predicate::{Element, Predicate};
 use bit_set::{self, BitSet};
+use std::ops::{BitAnd, BitOr, BitXor, Bound, RangeBounds, Sub};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Selection<'a> {
@@ -39,12 +40,23 @@ impl<'a> Selection<'a> {
             recur(&node, &mut bit_set);
         }
 
+        let matches = |&index: &usize| p.matches(&self.document.nth(index).unwrap());
+        let candidates = self
+            .document
+            .index
+            .get()
+            .and_then(|index| index.candidates(&p.hint()));
         return Selection {
             document: self.document,
-            bit_set: bit_set
-                .iter()
-                .filter(|&index| p.matches(&self.document.nth(index).unwrap()))
-                .collect(),
+            bit_set: match candidates {
+                Some(candidates) => candidates
+                    .iter()
+                    .copied()
+                    .filter(|&index| bit_set.contains(index))
+                    .filter(matches)
+                    .collect(),
+                None => bit_set.iter().filter(matches).collect(),
+            },
         };
 
         fn recur(node: &Node, bit_set: &mut BitSet) {
@@ -91,10 +103,11 @@ impl<'a> Selection<'a> {
 
     pub fn parents(&self) -> Selection<'a> {
         let mut bit_set = BitSet::new();
-        for mut node in self {
-            while let Some(parent) = node.parent() {
-                bit_set.insert(parent.index());
-                node = parent;
+        for node in self {
+            for ancestor in node.ancestors() {
+                if !bit_set.insert(ancestor.index()) {
+                    break;
+                }
             }
         }
 
@@ -118,6 +131,158 @@ impl<'a> Selection<'a> {
         }
     }
 
+    /// Returns the closest ancestor-or-self of each node matching `p`.
+    pub fn closest<P: Predicate>(&self, p: P) -> Selection<'a> {
+        Selection {
+            document: self.document,
+            bit_set: self
+                .iter()
+                .filter_map(|node| {
+                    node.closest(|node: &Node| p.matches(node))
+                        .map(|node| node.index())
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns the other children of each node's parent.
+    pub fn siblings(&self) -> Selection<'a> {
+        let mut bit_set = self.prev_all().bit_set;
+        bit_set.union_with(&self.next_all().bit_set);
+        Selection {
+            document: self.document,
+            bit_set,
+        }
+    }
+
+    /// Returns all following siblings of each node.
+    pub fn next_all(&self) -> Selection<'a> {
+        self.next_until(|_: &Node| false)
+    }
+
+    /// Returns all preceding siblings of each node.
+    pub fn prev_all(&self) -> Selection<'a> {
+        self.prev_until(|_: &Node| false)
+    }
+
+    /// Returns the following siblings of each node, up to but not including
+    /// the first matching `p`.
+    pub fn next_until<P: Predicate>(&self, p: P) -> Selection<'a> {
+        self.walk(|node| node.next(), p)
+    }
+
+    /// Returns the preceding siblings of each node, up to but not including
+    /// the first matching `p`.
+    pub fn prev_until<P: Predicate>(&self, p: P) -> Selection<'a> {
+        self.walk(|node| node.prev(), p)
+    }
+
+    /// Returns the ancestors of each node, up to but not including the first
+    /// matching `p`.
+    pub fn parents_until<P: Predicate>(&self, p: P) -> Selection<'a> {
+        self.walk(|node| node.parent(), p)
+    }
+
+    fn walk<F, P>(&self, step: F, p: P) -> Selection<'a>
+    where
+        F: Fn(&Node<'a>) -> Option<Node<'a>>,
+        P: Predicate,
+    {
+        let mut bit_set = BitSet::new();
+        for node in self {
+            let mut current = step(&node);
+            while let Some(node) = current {
+                if p.matches(&node) {
+                    break;
+                }
+                bit_set.insert(node.index());
+                current = step(&node);
+            }
+        }
+        Selection {
+            document: self.document,
+            bit_set,
+        }
+    }
+
+    /// Returns the nodes which have a descendant matching `p`.
+    pub fn has<P: Predicate>(&self, p: P) -> Selection<'a> {
+        self.filter(|node: &Node| node.descendants().any(|node| p.matches(&node)))
+    }
+
+    /// Returns the nodes not matching `p`.
+    pub fn not<P: Predicate>(&self, p: P) -> Selection<'a> {
+        self.filter(|node: &Node| !p.matches(node))
+    }
+
+    /// Returns a selection of the `n`th node, indexed from 0, or an empty
+    /// selection if there are not that many nodes.
+    pub fn get(&self, n: usize) -> Selection<'a> {
+        self.slice(n..=n)
+    }
+
+    /// Returns a selection of the nodes at the positions in `range`.
+    pub fn slice<R: RangeBounds<usize>>(&self, range: R) -> Selection<'a> {
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start.saturating_add(1),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end.saturating_add(1),
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => usize::MAX,
+        };
+        Selection {
+            document: self.document,
+            bit_set: self
+                .bit_set
+                .iter()
+                .skip(start)
+                .take(end.saturating_sub(start))
+                .collect(),
+        }
+    }
+
+    /// Returns the nodes in this selection or `other`. The same as `union`.
+    pub fn add(&self, other: &Selection<'a>) -> Selection<'a> {
+        self.union(other)
+    }
+
+    /// Returns the next sibling element of each node, skipping text and
+    /// comments.
+    pub fn next_element(&self) -> Selection<'a> {
+        Selection {
+            document: self.document,
+            bit_set: self
+                .iter()
+                .filter_map(|node| node.next_element().map(|next| next.index()))
+                .collect(),
+        }
+    }
+
+    /// Returns the previous sibling element of each node, skipping text and
+    /// comments.
+    pub fn prev_element(&self) -> Selection<'a> {
+        Selection {
+            document: self.document,
+            bit_set: self
+                .iter()
+                .filter_map(|node| node.prev_element().map(|prev| prev.index()))
+                .collect(),
+        }
+    }
+
+    /// Returns the child elements of each node.
+    pub fn element_children(&self) -> Selection<'a> {
+        self.children().filter(Element)
+    }
+
+    /// Returns the sibling elements of each node.
+    pub fn element_siblings(&self) -> Selection<'a> {
+        self.siblings().filter(Element)
+    }
+
     pub fn first(&self) -> Option<Node<'a>> {
         self.bit_set
             .iter()
@@ -139,8 +304,87 @@ impl<'a> Selection<'a> {
     pub fn is_empty(&self) -> bool {
         self.bit_set.is_empty()
     }
+
+    /// Returns whether `node` is in this selection.
+    pub fn contains(&self, node: &Node) -> bool {
+        std::ptr::eq(self.document, node.document()) && self.bit_set.contains(node.index())
+    }
+
+    /// Returns the nodes in either selection.
+    ///
+    /// # Panics
+    ///
+    /// If the selections are of different documents.
+    pub fn union(&self, other: &Selection<'a>) -> Selection<'a> {
+        self.combine(other, BitSet::union_with)
+    }
+
+    /// Returns the nodes in both selections.
+    ///
+    /// # Panics
+    ///
+    /// If the selections are of different documents.
+    pub fn intersection(&self, other: &Selection<'a>) -> Selection<'a> {
+        self.combine(other, BitSet::intersect_with)
+    }
+
+    /// Returns the nodes in this selection but not in `other`.
+    ///
+    /// # Panics
+    ///
+    /// If the selections are of different documents.
+    pub fn difference(&self, other: &Selection<'a>) -> Selection<'a> {
+        self.combine(other, BitSet::difference_with)
+    }
+
+    /// Returns the nodes in exactly one of the selections.
+    ///
+    /// # Panics
+    ///
+    /// If the selections are of different documents.
+    pub fn symmetric_difference(&self, other: &Selection<'a>) -> Selection<'a> {
+        self.combine(other, BitSet::symmetric_difference_with)
+    }
+
+    fn combine(&self, other: &Selection<'a>, f: fn(&mut BitSet, &BitSet)) -> Selection<'a> {
+        assert!(
+            std::ptr::eq(self.document, other.document),
+            "cannot combine selections of different documents"
+        );
+        let mut bit_set = self.bit_set.clone();
+        f(&mut bit_set, &other.bit_set);
+        Selection {
+            document: self.document,
+            bit_set,
+        }
+    }
+}
+
+macro_rules! operator {
+    ($trait:ident, $method:ident, $function:ident) => {
+        impl<'a, 'b> $trait<&'b Selection<'a>> for &'b Selection<'a> {
+            type Output = Selection<'a>;
+
+            fn $method(self, other: &'b Selection<'a>) -> Selection<'a> {
+                self.$function(other)
+            }
+        }
+
+        impl<'a> $trait for Selection<'a> {
+            type Output = Selection<'a>;
+
+            fn $method(self, other: Selection<'a>) -> Selection<'a> {
+                self.$function(&other)
+            }
+        }
+    };
 }
 
+operator!(BitOr, bitor, union);
+operator!(BitAnd, bitand, intersection);
+operator!(Sub, sub, difference);
+operator!(BitXor, bitxor, symmetric_difference);
+
 #[derive(Clone)]
 pub struct Iter<'sel, 'doc: 'sel> {
     selection: &'sel Selection<'doc>,
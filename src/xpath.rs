@@ -0,0 +1,1571 @@
+//! An XPath 1.0 query engine.
+//!
+//! Expressions are compiled with `XPath::parse` and evaluated against a `Node`
+//! or the root of a `Document`:
+//!
+//! ```
+//! use select::document::Document;
+//! use select::xpath::{Value, XPath};
+//!
+//! let document = Document::from("<table id=x><tr><td>a<td>b</tr><tr><td>c<td>d</tr></table>");
+//! let cells = XPath::parse("//table[@id='x']//tr[position()>1]/td[2]/text()").unwrap();
+//! match cells.evaluate_document(&document).unwrap() {
+//!     Value::Nodes(selection) => assert_eq!(selection.first().unwrap().text(), "d"),
+//!     _ => unreachable!(),
+//! }
+//!
+//! let count = XPath::parse("count(//td)").unwrap();
+//! assert_eq!(count.evaluate_document(&document).unwrap(), Value::Number(4.0));
+//! ```
+//!
+//! Elements and attributes are matched by their local name; namespace prefixes
+//! are ignored. Doctypes are not part of the XPath data model and are never
+//! selected. Variables and the `namespace` axis are not supported.
+
+use std::cell::OnceCell;
+use std::rc::Rc;
+use std::{error, fmt};
+
+use crate::document::Document;
+use crate::node::{Data, Node};
+use crate::selection::Selection;
+
+/// A compiled XPath expression.
+pub struct XPath {
+    source: String,
+    expr: Expr,
+}
+
+impl XPath {
+    /// Parses and compiles an XPath 1.0 expression.
+    pub fn parse(source: &str) -> Result<XPath, ParseError> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser {
+            tokens: &tokens,
+            position: 0,
+            end: source.len(),
+        };
+        let expr = parser.expr()?;
+        if parser.position < tokens.len() {
+            return parser.error("unexpected token");
+        }
+        Ok(XPath {
+            source: source.into(),
+            expr,
+        })
+    }
+
+    /// Get the source string this expression was parsed from.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Evaluates the expression with `node` as the context node.
+    pub fn evaluate<'a>(&self, node: &Node<'a>) -> Result<Value<'a>, EvalError> {
+        let document = node.document();
+        self.run(document, Item::Node(*node))
+    }
+
+    /// Evaluates the expression with the root of `document` as the context
+    /// node.
+    pub fn evaluate_document<'a>(&self, document: &'a Document) -> Result<Value<'a>, EvalError> {
+        self.run(document, Item::Root)
+    }
+
+    fn run<'a>(&self, document: &'a Document, item: Item<'a>) -> Result<Value<'a>, EvalError> {
+        let context = Context {
+            document,
+            order: Rc::default(),
+            item,
+            position: 1,
+            size: 1,
+        };
+        Ok(match context.eval(&self.expr)? {
+            Object::Nodes(items) => {
+                let attributes = items
+                    .iter()
+                    .filter_map(|&item| match item {
+                        Item::Attribute(element, index) => Some(Attribute::new(element, index)),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                if attributes.is_empty() {
+                    Value::Nodes(Selection::new(
+                        document,
+                        items
+                            .iter()
+                            .filter_map(|&item| match item {
+                                Item::Node(node) => Some(node.index()),
+                                _ => None,
+                            })
+                            .collect(),
+                    ))
+                } else {
+                    Value::Attributes(attributes)
+                }
+            }
+            Object::Boolean(boolean) => Value::Boolean(boolean),
+            Object::Number(number) => Value::Number(number),
+            Object::String(string) => Value::String(string),
+        })
+    }
+}
+
+impl fmt::Debug for XPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("XPath").field(&self.source).finish()
+    }
+}
+
+/// The result of evaluating an XPath expression.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value<'a> {
+    /// A node-set. The root node is not included as it is not a `Node`.
+    Nodes(Selection<'a>),
+    /// A node-set containing attributes, in document order. Any other nodes
+    /// in the node-set are not included.
+    Attributes(Vec<Attribute<'a>>),
+    Boolean(bool),
+    Number(f64),
+    String(String),
+}
+
+impl<'a> Value<'a> {
+    /// Converts the value to a boolean, as the XPath `boolean()` function
+    /// would.
+    pub fn boolean(&self) -> bool {
+        match *self {
+            Value::Nodes(ref selection) => !selection.is_empty(),
+            Value::Attributes(ref attributes) => !attributes.is_empty(),
+            Value::Boolean(boolean) => boolean,
+            Value::Number(number) => number != 0.0 && !number.is_nan(),
+            Value::String(ref string) => !string.is_empty(),
+        }
+    }
+
+    /// Converts the value to a number, as the XPath `number()` function would.
+    pub fn number(&self) -> f64 {
+        match *self {
+            Value::Boolean(boolean) => boolean as u8 as f64,
+            Value::Number(number) => number,
+            _ => string_to_number(&self.string()),
+        }
+    }
+
+    /// Converts the value to a string, as the XPath `string()` function would.
+    /// For node-sets, this is the string-value of the first node.
+    pub fn string(&self) -> String {
+        match *self {
+            Value::Nodes(ref selection) => selection
+                .first()
+                .map_or_else(String::new, |node| string_value(Item::Node(node))),
+            Value::Attributes(ref attributes) => attributes
+                .first()
+                .map_or_else(String::new, |attribute| attribute.value.into()),
+            Value::Boolean(boolean) => boolean.to_string(),
+            Value::Number(number) => number_to_string(number),
+            Value::String(ref string) => string.clone(),
+        }
+    }
+}
+
+/// An attribute node selected by an XPath expression.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Attribute<'a> {
+    pub element: Node<'a>,
+    pub name: &'a str,
+    pub value: &'a str,
+}
+
+impl<'a> Attribute<'a> {
+    fn new(element: Node<'a>, index: usize) -> Attribute<'a> {
+        let (name, value) = element.attrs().nth(index).unwrap();
+        Attribute {
+            element,
+            name,
+            value,
+        }
+    }
+}
+
+/// An error encountered while parsing an XPath expression.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// The byte offset in the source string at which the error occurred.
+    pub position: usize,
+    pub message: &'static str,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl error::Error for ParseError {}
+
+/// An error encountered while evaluating an XPath expression, such as using a
+/// string where a node-set is required.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EvalError {
+    pub message: &'static str,
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.message)
+    }
+}
+
+impl error::Error for EvalError {}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Slash,
+    DoubleSlash,
+    Pipe,
+    Plus,
+    Minus,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    LeftParen,
+    RightParen,
+    LeftBracket,
+    RightBracket,
+    Dot,
+    DoubleDot,
+    At,
+    Comma,
+    DoubleColon,
+    Multiply,
+    And,
+    Or,
+    Mod,
+    Div,
+    Literal(String),
+    Number(f64),
+    /// A name test: `*`, `prefix:*` (stored as `*`), or a name with any prefix
+    /// removed.
+    NameTest(String),
+    NodeType(String),
+    FunctionName(String),
+    AxisName(String),
+}
+
+impl Token {
+    fn is_operator(&self) -> bool {
+        matches!(
+            *self,
+            Token::Slash
+                | Token::DoubleSlash
+                | Token::Pipe
+                | Token::Plus
+                | Token::Minus
+                | Token::Equal
+                | Token::NotEqual
+                | Token::Less
+                | Token::LessEqual
+                | Token::Greater
+                | Token::GreaterEqual
+                | Token::Multiply
+                | Token::And
+                | Token::Or
+                | Token::Mod
+                | Token::Div
+        )
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, ParseError> {
+    let error = |position, message| Err(ParseError { position, message });
+    let mut tokens: Vec<(Token, usize)> = vec![];
+    let bytes = source.as_bytes();
+    let mut position = 0;
+
+    while position < bytes.len() {
+        let start = position;
+        let rest = &source[position..];
+        let c = rest.chars().next().unwrap();
+
+        if c.is_ascii_whitespace() {
+            position += 1;
+            continue;
+        }
+
+        // An operator is expected if there is a preceding token which is not
+        // one of `@`, `::`, `(`, `[`, `,` or an operator.
+        let operator_expected = tokens.last().is_some_and(|(token, _)| {
+            !matches!(
+                *token,
+                Token::At
+                    | Token::DoubleColon
+                    | Token::LeftParen
+                    | Token::LeftBracket
+                    | Token::Comma
+            ) && !token.is_operator()
+        });
+
+        let two = rest.get(..2).unwrap_or("");
+        let token = match two {
+            "//" => Some(Token::DoubleSlash),
+            "!=" => Some(Token::NotEqual),
+            "<=" => Some(Token::LessEqual),
+            ">=" => Some(Token::GreaterEqual),
+            ".." => Some(Token::DoubleDot),
+            "::" => Some(Token::DoubleColon),
+            _ => None,
+        };
+        if let Some(token) = token {
+            tokens.push((token, start));
+            position += 2;
+            continue;
+        }
+
+        let token = match c {
+            '/' => Some(Token::Slash),
+            '|' => Some(Token::Pipe),
+            '+' => Some(Token::Plus),
+            '-' => Some(Token::Minus),
+            '=' => Some(Token::Equal),
+            '<' => Some(Token::Less),
+            '>' => Some(Token::Greater),
+            '(' => Some(Token::LeftParen),
+            ')' => Some(Token::RightParen),
+            '[' => Some(Token::LeftBracket),
+            ']' => Some(Token::RightBracket),
+            '@' => Some(Token::At),
+            ',' => Some(Token::Comma),
+            '*' if operator_expected => Some(Token::Multiply),
+            '*' => Some(Token::NameTest("*".into())),
+            '$' => return error(start, "variables are not supported"),
+            _ => None,
+        };
+        if let Some(token) = token {
+            tokens.push((token, start));
+            position += 1;
+            continue;
+        }
+
+        if c == '"' || c == '\'' {
+            match rest[1..].find(c) {
+                Some(end) => {
+                    tokens.push((Token::Literal(rest[1..end + 1].into()), start));
+                    position += end + 2;
+                }
+                None => return error(start, "unterminated string literal"),
+            }
+            continue;
+        }
+
+        if c.is_ascii_digit() || c == '.' && rest[1..].starts_with(|c: char| c.is_ascii_digit()) {
+            let len = rest
+                .find(|c: char| !c.is_ascii_digit() && c != '.')
+                .unwrap_or(rest.len());
+            match rest[..len].parse() {
+                Ok(number) => tokens.push((Token::Number(number), start)),
+                Err(_) => return error(start, "invalid number"),
+            }
+            position += len;
+            continue;
+        }
+
+        if c == '.' {
+            tokens.push((Token::Dot, start));
+            position += 1;
+            continue;
+        }
+
+        if !is_name_start(c) {
+            return error(start, "unexpected character");
+        }
+
+        let len = name_len(rest);
+        let name = &rest[..len];
+        position += len;
+
+        if operator_expected {
+            let token = match name {
+                "and" => Token::And,
+                "or" => Token::Or,
+                "mod" => Token::Mod,
+                "div" => Token::Div,
+                _ => return error(start, "expected operator"),
+            };
+            tokens.push((token, start));
+            continue;
+        }
+
+        // A QName or `prefix:*`.
+        let mut local = name;
+        let after = &source[position..];
+        if after.starts_with(':') && !after.starts_with("::") {
+            let after = &after[1..];
+            if after.starts_with('*') {
+                tokens.push((Token::NameTest("*".into()), start));
+                position += 2;
+                continue;
+            }
+            if after.starts_with(is_name_start) {
+                let len = name_len(after);
+                local = &after[..len];
+                position += 1 + len;
+            }
+        }
+
+        let after = source[position..].trim_start();
+        let token = if after.starts_with('(') {
+            match local {
+                "node" | "text" | "comment" | "processing-instruction" => {
+                    Token::NodeType(local.into())
+                }
+                _ => Token::FunctionName(local.into()),
+            }
+        } else if after.starts_with("::") {
+            Token::AxisName(local.into())
+        } else {
+            Token::NameTest(local.into())
+        };
+        tokens.push((token, start));
+    }
+
+    Ok(tokens)
+}
+
+fn is_name_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn name_len(source: &str) -> usize {
+    source
+        .find(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '-' | '.')))
+        .unwrap_or(source.len())
+}
+
+#[derive(Clone, Debug)]
+enum Expr {
+    Or(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Compare(Compare, Box<Expr>, Box<Expr>),
+    Arithmetic(Arithmetic, Box<Expr>, Box<Expr>),
+    Negate(Box<Expr>),
+    Union(Box<Expr>, Box<Expr>),
+    /// A location path, relative to the context node or, if absolute, the
+    /// root.
+    Path(bool, Vec<Step>),
+    /// A filter expression: a primary expression followed by predicates and a
+    /// relative location path.
+    Filter(Box<Expr>, Vec<Expr>, Vec<Step>),
+    Literal(String),
+    Number(f64),
+    Function(Function, Vec<Expr>),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Compare {
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Arithmetic {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+}
+
+#[derive(Clone, Debug)]
+struct Step {
+    axis: Axis,
+    test: NodeTest,
+    predicates: Vec<Expr>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Axis {
+    Ancestor,
+    AncestorOrSelf,
+    Attribute,
+    Child,
+    Descendant,
+    DescendantOrSelf,
+    Following,
+    FollowingSibling,
+    Parent,
+    Preceding,
+    PrecedingSibling,
+    SelfNode,
+}
+
+#[derive(Clone, Debug)]
+enum NodeTest {
+    /// `*` or a name.
+    Name(String),
+    Node,
+    Text,
+    Comment,
+    ProcessingInstruction(Option<String>),
+}
+
+macro_rules! functions {
+    ($($variant:ident $name:literal $min:literal $max:expr,)*) => {
+        #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+        enum Function {
+            $($variant,)*
+        }
+
+        impl Function {
+            /// Looks up a function by name, returning it and the minimum and
+            /// maximum number of arguments it accepts.
+            fn lookup(name: &str) -> Option<(Function, usize, usize)> {
+                match name {
+                    $($name => Some((Function::$variant, $min, $max)),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+functions! {
+    Last "last" 0 0,
+    Position "position" 0 0,
+    Count "count" 1 1,
+    Id "id" 1 1,
+    LocalName "local-name" 0 1,
+    NamespaceUri "namespace-uri" 0 1,
+    Name "name" 0 1,
+    String "string" 0 1,
+    Concat "concat" 2 usize::MAX,
+    StartsWith "starts-with" 2 2,
+    Contains "contains" 2 2,
+    SubstringBefore "substring-before" 2 2,
+    SubstringAfter "substring-after" 2 2,
+    Substring "substring" 2 3,
+    StringLength "string-length" 0 1,
+    NormalizeSpace "normalize-space" 0 1,
+    Translate "translate" 3 3,
+    Boolean "boolean" 1 1,
+    Not "not" 1 1,
+    True "true" 0 0,
+    False "false" 0 0,
+    Lang "lang" 1 1,
+    Number "number" 0 1,
+    Sum "sum" 1 1,
+    Floor "floor" 1 1,
+    Ceiling "ceiling" 1 1,
+    Round "round" 1 1,
+}
+
+struct Parser<'a> {
+    tokens: &'a [(Token, usize)],
+    position: usize,
+    /// The length of the source, reported as the position of errors at the
+    /// end of input.
+    end: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error<T>(&self, message: &'static str) -> Result<T, ParseError> {
+        let position = self
+            .tokens
+            .get(self.position)
+            .map_or(self.end, |&(_, position)| position);
+        Err(ParseError { position, message })
+    }
+
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &Token, message: &'static str) -> Result<(), ParseError> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            self.error(message)
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.and_expr()?;
+        while self.eat(&Token::Or) {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and_expr()?));
+        }
+        Ok(expr)
+    }
+
+    fn and_expr(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.equality_expr()?;
+        while self.eat(&Token::And) {
+            expr = Expr::And(Box::new(expr), Box::new(self.equality_expr()?));
+        }
+        Ok(expr)
+    }
+
+    fn equality_expr(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.relational_expr()?;
+        loop {
+            let compare = match self.peek() {
+                Some(Token::Equal) => Compare::Equal,
+                Some(Token::NotEqual) => Compare::NotEqual,
+                _ => return Ok(expr),
+            };
+            self.position += 1;
+            expr = Expr::Compare(compare, Box::new(expr), Box::new(self.relational_expr()?));
+        }
+    }
+
+    fn relational_expr(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.additive_expr()?;
+        loop {
+            let compare = match self.peek() {
+                Some(Token::Less) => Compare::Less,
+                Some(Token::LessEqual) => Compare::LessEqual,
+                Some(Token::Greater) => Compare::Greater,
+                Some(Token::GreaterEqual) => Compare::GreaterEqual,
+                _ => return Ok(expr),
+            };
+            self.position += 1;
+            expr = Expr::Compare(compare, Box::new(expr), Box::new(self.additive_expr()?));
+        }
+    }
+
+    fn additive_expr(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.multiplicative_expr()?;
+        loop {
+            let arithmetic = match self.peek() {
+                Some(Token::Plus) => Arithmetic::Add,
+                Some(Token::Minus) => Arithmetic::Subtract,
+                _ => return Ok(expr),
+            };
+            self.position += 1;
+            let right = self.multiplicative_expr()?;
+            expr = Expr::Arithmetic(arithmetic, Box::new(expr), Box::new(right));
+        }
+    }
+
+    fn multiplicative_expr(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.unary_expr()?;
+        loop {
+            let arithmetic = match self.peek() {
+                Some(Token::Multiply) => Arithmetic::Multiply,
+                Some(Token::Div) => Arithmetic::Divide,
+                Some(Token::Mod) => Arithmetic::Modulo,
+                _ => return Ok(expr),
+            };
+            self.position += 1;
+            expr = Expr::Arithmetic(arithmetic, Box::new(expr), Box::new(self.unary_expr()?));
+        }
+    }
+
+    fn unary_expr(&mut self) -> Result<Expr, ParseError> {
+        if self.eat(&Token::Minus) {
+            Ok(Expr::Negate(Box::new(self.unary_expr()?)))
+        } else {
+            self.union_expr()
+        }
+    }
+
+    fn union_expr(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.path_expr()?;
+        while self.eat(&Token::Pipe) {
+            expr = Expr::Union(Box::new(expr), Box::new(self.path_expr()?));
+        }
+        Ok(expr)
+    }
+
+    fn path_expr(&mut self) -> Result<Expr, ParseError> {
+        match self.peek() {
+            Some(
+                Token::Literal(_) | Token::Number(_) | Token::FunctionName(_) | Token::LeftParen,
+            ) => {
+                let primary = self.primary_expr()?;
+                let mut predicates = vec![];
+                while self.peek() == Some(&Token::LeftBracket) {
+                    predicates.push(self.predicate()?);
+                }
+                let steps = match self.peek() {
+                    Some(Token::Slash | Token::DoubleSlash) => self.relative_path(true)?,
+                    _ => vec![],
+                };
+                if predicates.is_empty() && steps.is_empty() {
+                    Ok(primary)
+                } else {
+                    Ok(Expr::Filter(Box::new(primary), predicates, steps))
+                }
+            }
+            Some(Token::Slash) => {
+                self.position += 1;
+                // `/` on its own selects the root.
+                if self.starts_step() {
+                    Ok(Expr::Path(true, self.relative_path(false)?))
+                } else {
+                    Ok(Expr::Path(true, vec![]))
+                }
+            }
+            Some(Token::DoubleSlash) => Ok(Expr::Path(true, self.relative_path(true)?)),
+            _ => Ok(Expr::Path(false, self.relative_path(false)?)),
+        }
+    }
+
+    fn starts_step(&self) -> bool {
+        matches!(
+            self.peek(),
+            Some(
+                Token::Dot
+                    | Token::DoubleDot
+                    | Token::At
+                    | Token::AxisName(_)
+                    | Token::NameTest(_)
+                    | Token::NodeType(_)
+            )
+        )
+    }
+
+    /// Parses steps separated by `/` or `//`. If `separated`, the path starts
+    /// with a separator.
+    fn relative_path(&mut self, separated: bool) -> Result<Vec<Step>, ParseError> {
+        let mut steps = vec![];
+        let mut separator = separated;
+        loop {
+            if separator {
+                match self.peek() {
+                    Some(Token::Slash) => {}
+                    Some(Token::DoubleSlash) => steps.push(Step {
+                        axis: Axis::DescendantOrSelf,
+                        test: NodeTest::Node,
+                        predicates: vec![],
+                    }),
+                    _ => return Ok(steps),
+                }
+                self.position += 1;
+            }
+            steps.push(self.step()?);
+            separator = true;
+        }
+    }
+
+    fn step(&mut self) -> Result<Step, ParseError> {
+        let abbreviated = |axis| Step {
+            axis,
+            test: NodeTest::Node,
+            predicates: vec![],
+        };
+        if self.eat(&Token::Dot) {
+            return Ok(abbreviated(Axis::SelfNode));
+        }
+        if self.eat(&Token::DoubleDot) {
+            return Ok(abbreviated(Axis::Parent));
+        }
+
+        let axis = match self.peek() {
+            Some(Token::At) => {
+                self.position += 1;
+                Axis::Attribute
+            }
+            Some(Token::AxisName(name)) => {
+                let axis = match &**name {
+                    "ancestor" => Axis::Ancestor,
+                    "ancestor-or-self" => Axis::AncestorOrSelf,
+                    "attribute" => Axis::Attribute,
+                    "child" => Axis::Child,
+                    "descendant" => Axis::Descendant,
+                    "descendant-or-self" => Axis::DescendantOrSelf,
+                    "following" => Axis::Following,
+                    "following-sibling" => Axis::FollowingSibling,
+                    "parent" => Axis::Parent,
+                    "preceding" => Axis::Preceding,
+                    "preceding-sibling" => Axis::PrecedingSibling,
+                    "self" => Axis::SelfNode,
+                    "namespace" => return self.error("the namespace axis is not supported"),
+                    _ => return self.error("unknown axis"),
+                };
+                self.position += 2;
+                axis
+            }
+            _ => Axis::Child,
+        };
+
+        let test = match self.peek() {
+            Some(Token::NameTest(name)) => {
+                self.position += 1;
+                NodeTest::Name(name.clone())
+            }
+            Some(Token::NodeType(name)) => {
+                self.position += 1;
+                self.expect(&Token::LeftParen, "expected '('")?;
+                let test = match &**name {
+                    "node" => NodeTest::Node,
+                    "text" => NodeTest::Text,
+                    "comment" => NodeTest::Comment,
+                    _ => match self.peek() {
+                        Some(Token::Literal(target)) => {
+                            self.position += 1;
+                            NodeTest::ProcessingInstruction(Some(target.clone()))
+                        }
+                        _ => NodeTest::ProcessingInstruction(None),
+                    },
+                };
+                self.expect(&Token::RightParen, "expected ')'")?;
+                test
+            }
+            _ => return self.error("expected node test"),
+        };
+
+        let mut predicates = vec![];
+        while self.peek() == Some(&Token::LeftBracket) {
+            predicates.push(self.predicate()?);
+        }
+
+        Ok(Step {
+            axis,
+            test,
+            predicates,
+        })
+    }
+
+    fn predicate(&mut self) -> Result<Expr, ParseError> {
+        self.expect(&Token::LeftBracket, "expected '['")?;
+        let expr = self.expr()?;
+        self.expect(&Token::RightBracket, "expected ']'")?;
+        Ok(expr)
+    }
+
+    fn primary_expr(&mut self) -> Result<Expr, ParseError> {
+        match self.peek() {
+            Some(Token::Literal(literal)) => {
+                self.position += 1;
+                Ok(Expr::Literal(literal.clone()))
+            }
+            Some(&Token::Number(number)) => {
+                self.position += 1;
+                Ok(Expr::Number(number))
+            }
+            Some(Token::LeftParen) => {
+                self.position += 1;
+                let expr = self.expr()?;
+                self.expect(&Token::RightParen, "expected ')'")?;
+                Ok(expr)
+            }
+            Some(Token::FunctionName(name)) => {
+                let (function, min, max) = match Function::lookup(name) {
+                    Some(function) => function,
+                    None => return self.error("unknown function"),
+                };
+                let start = self.position;
+                self.position += 1;
+                self.expect(&Token::LeftParen, "expected '('")?;
+                let mut args = vec![];
+                if !self.eat(&Token::RightParen) {
+                    loop {
+                        args.push(self.expr()?);
+                        if self.eat(&Token::RightParen) {
+                            break;
+                        }
+                        self.expect(&Token::Comma, "expected ',' or ')'")?;
+                    }
+                }
+                if args.len() < min || args.len() > max {
+                    self.position = start;
+                    return self.error("wrong number of arguments");
+                }
+                Ok(Expr::Function(function, args))
+            }
+            _ => self.error("expected expression"),
+        }
+    }
+}
+
+/// A node in the XPath data model.
+#[derive(Copy, Clone, Debug)]
+enum Item<'a> {
+    Root,
+    Node(Node<'a>),
+    /// The attribute of an element at the given index.
+    Attribute(Node<'a>, usize),
+}
+
+impl<'a> Item<'a> {
+    /// A key sorting items in document order, given the position of each
+    /// node: the root, then each node followed by its attributes. Nodes
+    /// outside the tree come last.
+    fn key(self, order: &[usize]) -> (usize, usize, usize, usize) {
+        match self {
+            Item::Root => (0, 0, 0, 0),
+            Item::Node(node) => (1, order[node.index()], node.index(), 0),
+            Item::Attribute(node, index) => (1, order[node.index()], node.index(), index + 1),
+        }
+    }
+
+    fn node(self) -> Option<Node<'a>> {
+        match self {
+            Item::Node(node) => Some(node),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Object<'a> {
+    Nodes(Vec<Item<'a>>),
+    Boolean(bool),
+    Number(f64),
+    String(String),
+}
+
+impl<'a> Object<'a> {
+    fn boolean(&self) -> bool {
+        match *self {
+            Object::Nodes(ref items) => !items.is_empty(),
+            Object::Boolean(boolean) => boolean,
+            Object::Number(number) => number != 0.0 && !number.is_nan(),
+            Object::String(ref string) => !string.is_empty(),
+        }
+    }
+
+    fn number(&self) -> f64 {
+        match *self {
+            Object::Boolean(boolean) => boolean as u8 as f64,
+            Object::Number(number) => number,
+            _ => string_to_number(&self.string()),
+        }
+    }
+
+    fn string(&self) -> String {
+        match *self {
+            Object::Nodes(ref items) => items
+                .first()
+                .map_or_else(String::new, |&item| string_value(item)),
+            Object::Boolean(boolean) => boolean.to_string(),
+            Object::Number(number) => number_to_string(number),
+            Object::String(ref string) => string.clone(),
+        }
+    }
+
+    fn into_nodes(self) -> Result<Vec<Item<'a>>, EvalError> {
+        match self {
+            Object::Nodes(items) => Ok(items),
+            _ => Err(EvalError {
+                message: "expected a node-set",
+            }),
+        }
+    }
+}
+
+fn string_value(item: Item) -> String {
+    match item {
+        Item::Root => String::new(),
+        Item::Node(node) => match *node.data() {
+            Data::Text(ref text) | Data::Comment(ref text) => String::from(&**text),
+            Data::ProcessingInstruction(_, ref data) => String::from(&**data),
+            Data::Element(..) => node.text(),
+            Data::Doctype(..) => String::new(),
+        },
+        Item::Attribute(node, index) => node.attrs().nth(index).unwrap().1.into(),
+    }
+}
+
+/// The string-value of the root: the text of all top-level nodes.
+fn root_string_value(document: &Document) -> String {
+    roots(document)
+        .map(|node| string_value(Item::Node(node)))
+        .collect()
+}
+
+fn string_to_number(string: &str) -> f64 {
+    let string = string.trim_matches(|c| matches!(c, ' ' | '\t' | '\r' | '\n'));
+    let digits = string.strip_prefix('-').unwrap_or(string);
+    let valid = !digits.is_empty()
+        && digits != "."
+        && digits.bytes().all(|b| b.is_ascii_digit() || b == b'.')
+        && digits.bytes().filter(|&b| b == b'.').count() <= 1;
+    if valid {
+        string.parse().unwrap_or(f64::NAN)
+    } else {
+        f64::NAN
+    }
+}
+
+fn number_to_string(number: f64) -> String {
+    if number.is_nan() {
+        "NaN".into()
+    } else if number.is_infinite() {
+        if number > 0.0 {
+            "Infinity"
+        } else {
+            "-Infinity"
+        }
+        .into()
+    } else if number == 0.0 {
+        "0".into()
+    } else {
+        number.to_string()
+    }
+}
+
+/// The top-level nodes of a document, which are the children of the root.
+fn roots(document: &Document) -> impl Iterator<Item = Node<'_>> {
+    let first = document
+        .first_root()
+        .map(|index| document.nth(index).unwrap());
+    std::iter::successors(first, |node| node.next())
+}
+
+/// Whether a node is part of the XPath data model.
+fn is_visible(node: &Node) -> bool {
+    !matches!(*node.data(), Data::Doctype(..))
+}
+
+struct Context<'a> {
+    document: &'a Document,
+    /// The position of each node in document order, by index, computed when
+    /// first needed as indices are out of order after mutation.
+    order: Rc<OnceCell<Vec<usize>>>,
+    item: Item<'a>,
+    position: usize,
+    size: usize,
+}
+
+impl<'a> Context<'a> {
+    fn with(&self, item: Item<'a>, position: usize, size: usize) -> Context<'a> {
+        Context {
+            document: self.document,
+            order: self.order.clone(),
+            item,
+            position,
+            size,
+        }
+    }
+
+    fn string_value(&self, item: Item<'a>) -> String {
+        match item {
+            Item::Root => root_string_value(self.document),
+            _ => string_value(item),
+        }
+    }
+
+    fn eval(&self, expr: &Expr) -> Result<Object<'a>, EvalError> {
+        Ok(match *expr {
+            Expr::Or(ref left, ref right) => {
+                Object::Boolean(self.eval(left)?.boolean() || self.eval(right)?.boolean())
+            }
+            Expr::And(ref left, ref right) => {
+                Object::Boolean(self.eval(left)?.boolean() && self.eval(right)?.boolean())
+            }
+            Expr::Compare(compare, ref left, ref right) => {
+                Object::Boolean(self.compare(compare, self.eval(left)?, self.eval(right)?))
+            }
+            Expr::Arithmetic(arithmetic, ref left, ref right) => {
+                let (left, right) = (self.eval(left)?.number(), self.eval(right)?.number());
+                Object::Number(match arithmetic {
+                    Arithmetic::Add => left + right,
+                    Arithmetic::Subtract => left - right,
+                    Arithmetic::Multiply => left * right,
+                    Arithmetic::Divide => left / right,
+                    Arithmetic::Modulo => left % right,
+                })
+            }
+            Expr::Negate(ref expr) => Object::Number(-self.eval(expr)?.number()),
+            Expr::Union(ref left, ref right) => {
+                let mut items = self.eval(left)?.into_nodes()?;
+                items.extend(self.eval(right)?.into_nodes()?);
+                Object::Nodes(self.sort(items))
+            }
+            Expr::Path(absolute, ref steps) => {
+                let start = if absolute { Item::Root } else { self.item };
+                Object::Nodes(self.steps(vec![start], steps)?)
+            }
+            Expr::Filter(ref primary, ref predicates, ref steps) => {
+                let mut items = self.sort(self.eval(primary)?.into_nodes()?);
+                for predicate in predicates {
+                    items = self.filter(items, predicate)?;
+                }
+                Object::Nodes(self.steps(items, steps)?)
+            }
+            Expr::Literal(ref literal) => Object::String(literal.clone()),
+            Expr::Number(number) => Object::Number(number),
+            Expr::Function(function, ref args) => self.call(function, args)?,
+        })
+    }
+
+    /// Filters `items`, in proximity order, by a predicate.
+    fn filter(&self, items: Vec<Item<'a>>, predicate: &Expr) -> Result<Vec<Item<'a>>, EvalError> {
+        let size = items.len();
+        let mut filtered = vec![];
+        for (index, item) in items.into_iter().enumerate() {
+            let matches = match self.with(item, index + 1, size).eval(predicate)? {
+                Object::Number(number) => number == (index + 1) as f64,
+                object => object.boolean(),
+            };
+            if matches {
+                filtered.push(item);
+            }
+        }
+        Ok(filtered)
+    }
+
+    fn steps(&self, mut items: Vec<Item<'a>>, steps: &[Step]) -> Result<Vec<Item<'a>>, EvalError> {
+        for step in steps {
+            let mut next = vec![];
+            for &item in &items {
+                let mut selected = axis(self.document, item, step.axis)
+                    .into_iter()
+                    .filter(|&item| test(item, step.axis, &step.test))
+                    .collect::<Vec<_>>();
+                for predicate in &step.predicates {
+                    selected = self.filter(selected, predicate)?;
+                }
+                next.extend(selected);
+            }
+            items = self.sort(next);
+        }
+        Ok(items)
+    }
+
+    /// Sorts items into document order, removing duplicates.
+    fn sort(&self, mut items: Vec<Item<'a>>) -> Vec<Item<'a>> {
+        if items.len() < 2 {
+            return items;
+        }
+        let order = self.order.get_or_init(|| {
+            let mut order = vec![usize::MAX; self.document.nodes.len()];
+            for (position, index) in self.document.tree_order().into_iter().enumerate() {
+                order[index] = position;
+            }
+            order
+        });
+        items.sort_by_key(|item| item.key(order));
+        items.dedup_by_key(|item| item.key(order));
+        items
+    }
+
+    fn compare(&self, compare: Compare, left: Object<'a>, right: Object<'a>) -> bool {
+        match (left, right) {
+            (Object::Nodes(left), Object::Nodes(right)) => {
+                let right = right
+                    .iter()
+                    .map(|&item| self.string_value(item))
+                    .collect::<Vec<_>>();
+                left.iter().any(|&item| {
+                    let left = self.string_value(item);
+                    right.iter().any(|right| {
+                        compare_atomic(
+                            compare,
+                            &Object::String(left.clone()),
+                            &Object::String(right.clone()),
+                        )
+                    })
+                })
+            }
+            // A node-set compared with a boolean is converted to a boolean.
+            (Object::Nodes(items), Object::Boolean(boolean)) => compare_atomic(
+                compare,
+                &Object::Boolean(!items.is_empty()),
+                &Object::Boolean(boolean),
+            ),
+            (Object::Boolean(boolean), Object::Nodes(items)) => compare_atomic(
+                compare,
+                &Object::Boolean(boolean),
+                &Object::Boolean(!items.is_empty()),
+            ),
+            (Object::Nodes(items), other) => items.iter().any(|&item| {
+                let value = self.node_as(item, &other);
+                compare_atomic(compare, &value, &other)
+            }),
+            (other, Object::Nodes(items)) => items.iter().any(|&item| {
+                let value = self.node_as(item, &other);
+                compare_atomic(compare, &other, &value)
+            }),
+            (left, right) => compare_atomic(compare, &left, &right),
+        }
+    }
+
+    /// Converts a node to the type of `other` for comparison.
+    fn node_as(&self, item: Item<'a>, other: &Object<'a>) -> Object<'a> {
+        match *other {
+            Object::Number(_) => Object::Number(string_to_number(&self.string_value(item))),
+            _ => Object::String(self.string_value(item)),
+        }
+    }
+
+    fn arg_string(&self, args: &[Expr], index: usize) -> Result<String, EvalError> {
+        match args.get(index) {
+            Some(arg) => Ok(match self.eval(arg)? {
+                Object::Nodes(items) => items
+                    .first()
+                    .map_or_else(String::new, |&item| self.string_value(item)),
+                object => object.string(),
+            }),
+            None => Ok(self.string_value(self.item)),
+        }
+    }
+
+    fn arg_number(&self, args: &[Expr], index: usize) -> Result<f64, EvalError> {
+        Ok(match args.get(index) {
+            Some(arg) => match self.eval(arg)? {
+                Object::Nodes(items) => string_to_number(
+                    &items
+                        .first()
+                        .map_or_else(String::new, |&item| self.string_value(item)),
+                ),
+                object => object.number(),
+            },
+            None => string_to_number(&self.string_value(self.item)),
+        })
+    }
+
+    /// The first node of the node-set argument, or the context node.
+    fn arg_item(&self, args: &[Expr]) -> Result<Option<Item<'a>>, EvalError> {
+        match args.first() {
+            Some(arg) => Ok(self.sort(self.eval(arg)?.into_nodes()?).first().copied()),
+            None => Ok(Some(self.item)),
+        }
+    }
+
+    fn call(&self, function: Function, args: &[Expr]) -> Result<Object<'a>, EvalError> {
+        Ok(match function {
+            Function::Last => Object::Number(self.size as f64),
+            Function::Position => Object::Number(self.position as f64),
+            Function::Count => Object::Number(self.eval(&args[0])?.into_nodes()?.len() as f64),
+            Function::Id => {
+                let ids = match self.eval(&args[0])? {
+                    Object::Nodes(items) => items
+                        .iter()
+                        .map(|&item| self.string_value(item))
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                    object => object.string(),
+                };
+                let ids = ids.split_whitespace().collect::<Vec<_>>();
+                Object::Nodes(
+                    self.document
+                        .nodes
+                        .iter()
+                        .filter(|raw| !self.document.is_detached(raw.index))
+                        .map(|raw| self.document.nth(raw.index).unwrap())
+                        .filter(|node| node.attr("id").is_some_and(|id| ids.contains(&id)))
+                        .map(Item::Node)
+                        .collect(),
+                )
+            }
+            Function::LocalName | Function::Name => Object::String(match self.arg_item(args)? {
+                Some(Item::Node(node)) => match *node.data() {
+                    Data::Element(ref name, _) => String::from(&*name.local),
+                    Data::ProcessingInstruction(ref target, _) => String::from(&**target),
+                    _ => String::new(),
+                },
+                Some(Item::Attribute(node, index)) => node.attrs().nth(index).unwrap().0.into(),
+                _ => String::new(),
+            }),
+            Function::NamespaceUri => Object::String(match self.arg_item(args)? {
+                Some(Item::Node(node)) => match *node.data() {
+                    Data::Element(ref name, _) => String::from(&*name.ns),
+                    _ => String::new(),
+                },
+                _ => String::new(),
+            }),
+            Function::String => Object::String(self.arg_string(args, 0)?),
+            Function::Concat => Object::String(
+                (0..args.len())
+                    .map(|index| self.arg_string(args, index))
+                    .collect::<Result<String, _>>()?,
+            ),
+            Function::StartsWith => {
+                let (string, prefix) = (self.arg_string(args, 0)?, self.arg_string(args, 1)?);
+                Object::Boolean(string.starts_with(&prefix))
+            }
+            Function::Contains => {
+                let (string, needle) = (self.arg_string(args, 0)?, self.arg_string(args, 1)?);
+                Object::Boolean(string.contains(&needle))
+            }
+            Function::SubstringBefore => {
+                let (string, needle) = (self.arg_string(args, 0)?, self.arg_string(args, 1)?);
+                Object::String(match string.find(&needle) {
+                    Some(index) => string[..index].into(),
+                    None => String::new(),
+                })
+            }
+            Function::SubstringAfter => {
+                let (string, needle) = (self.arg_string(args, 0)?, self.arg_string(args, 1)?);
+                Object::String(match string.find(&needle) {
+                    Some(index) => string[index + needle.len()..].into(),
+                    None => String::new(),
+                })
+            }
+            Function::Substring => {
+                let string = self.arg_string(args, 0)?;
+                let start = round(self.arg_number(args, 1)?);
+                let end = match args.get(2) {
+                    Some(_) => start + round(self.arg_number(args, 2)?),
+                    None => f64::INFINITY,
+                };
+                // Characters at positions p with start <= p < end, counting
+                // from 1.
+                Object::String(
+                    string
+                        .chars()
+                        .enumerate()
+                        .filter(|&(index, _)| {
+                            let position = (index + 1) as f64;
+                            position >= start && position < end
+                        })
+                        .map(|(_, c)| c)
+                        .collect(),
+                )
+            }
+            Function::StringLength => {
+                Object::Number(self.arg_string(args, 0)?.chars().count() as f64)
+            }
+            Function::NormalizeSpace => Object::String(
+                self.arg_string(args, 0)?
+                    .split([' ', '\t', '\r', '\n'])
+                    .filter(|word| !word.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ),
+            Function::Translate => {
+                let string = self.arg_string(args, 0)?;
+                let from = self.arg_string(args, 1)?.chars().collect::<Vec<_>>();
+                let to = self.arg_string(args, 2)?.chars().collect::<Vec<_>>();
+                Object::String(
+                    string
+                        .chars()
+                        .filter_map(|c| match from.iter().position(|&from| from == c) {
+                            Some(index) => to.get(index).copied(),
+                            None => Some(c),
+                        })
+                        .collect(),
+                )
+            }
+            Function::Boolean => Object::Boolean(self.eval(&args[0])?.boolean()),
+            Function::Not => Object::Boolean(!self.eval(&args[0])?.boolean()),
+            Function::True => Object::Boolean(true),
+            Function::False => Object::Boolean(false),
+            Function::Lang => {
+                let lang = self.arg_string(args, 0)?.to_ascii_lowercase();
+                let mut node = match self.item {
+                    Item::Node(node) => Some(node),
+                    Item::Attribute(node, _) => Some(node),
+                    Item::Root => None,
+                };
+                let mut value = None;
+                while let Some(current) = node {
+                    if let Some(attr) = current.attr("xml:lang").or_else(|| current.attr("lang")) {
+                        value = Some(attr.to_ascii_lowercase());
+                        break;
+                    }
+                    node = current.parent();
+                }
+                Object::Boolean(value.is_some_and(|value| {
+                    value == lang
+                        || value.starts_with(&lang) && value[lang.len()..].starts_with('-')
+                }))
+            }
+            Function::Number => Object::Number(self.arg_number(args, 0)?),
+            Function::Sum => Object::Number(
+                self.eval(&args[0])?
+                    .into_nodes()?
+                    .iter()
+                    .map(|&item| string_to_number(&self.string_value(item)))
+                    .sum(),
+            ),
+            Function::Floor => Object::Number(self.arg_number(args, 0)?.floor()),
+            Function::Ceiling => Object::Number(self.arg_number(args, 0)?.ceil()),
+            Function::Round => Object::Number(round(self.arg_number(args, 0)?)),
+        })
+    }
+}
+
+/// Rounds to the nearest integer, rounding halves towards positive infinity.
+fn round(number: f64) -> f64 {
+    if number.is_nan() || number.is_infinite() {
+        number
+    } else if (-0.5..0.0).contains(&number) {
+        -0.0
+    } else {
+        (number + 0.5).floor()
+    }
+}
+
+fn compare_atomic(compare: Compare, left: &Object, right: &Object) -> bool {
+    match compare {
+        Compare::Equal | Compare::NotEqual => {
+            let equal = match (left, right) {
+                (Object::Boolean(_), _) | (_, Object::Boolean(_)) => {
+                    left.boolean() == right.boolean()
+                }
+                (Object::Number(_), _) | (_, Object::Number(_)) => left.number() == right.number(),
+                _ => left.string() == right.string(),
+            };
+            equal == (compare == Compare::Equal)
+        }
+        _ => {
+            let (left, right) = (left.number(), right.number());
+            match compare {
+                Compare::Less => left < right,
+                Compare::LessEqual => left <= right,
+                Compare::Greater => left > right,
+                _ => left >= right,
+            }
+        }
+    }
+}
+
+fn test(item: Item, axis: Axis, test: &NodeTest) -> bool {
+    match *test {
+        NodeTest::Node => true,
+        NodeTest::Name(ref name) => {
+            // The principal node type is attribute on the attribute axis and
+            // element otherwise.
+            let actual = match item {
+                Item::Attribute(node, index) if axis == Axis::Attribute => {
+                    node.attrs().nth(index).unwrap().0
+                }
+                Item::Node(node) if axis != Axis::Attribute => match node.name() {
+                    Some(name) => name,
+                    None => return false,
+                },
+                _ => return false,
+            };
+            name == "*" || name == actual
+        }
+        NodeTest::Text => item
+            .node()
+            .is_some_and(|node| matches!(*node.data(), Data::Text(..))),
+        NodeTest::Comment => item
+            .node()
+            .is_some_and(|node| matches!(*node.data(), Data::Comment(..))),
+        NodeTest::ProcessingInstruction(ref target) => {
+            item.node().is_some_and(|node| match *node.data() {
+                Data::ProcessingInstruction(ref actual, _) => {
+                    target.as_ref().is_none_or(|target| **target == **actual)
+                }
+                _ => false,
+            })
+        }
+    }
+}
+
+/// The items on `axis` from `item`, in proximity order: document order for
+/// forward axes and reverse document order for reverse axes.
+fn axis<'a>(document: &'a Document, item: Item<'a>, axis: Axis) -> Vec<Item<'a>> {
+    let children = |item: Item<'a>| -> Vec<Node<'a>> {
+        match item {
+            Item::Root => roots(document).collect(),
+            Item::Node(node) => node.children().collect(),
+            Item::Attribute(..) => vec![],
+        }
+    };
+    let descendants = |item: Item<'a>| -> Vec<Node<'a>> {
+        match item {
+            Item::Root => roots(document)
+                .flat_map(|node| std::iter::once(node).chain(node.descendants()))
+                .collect(),
+            Item::Node(node) => node.descendants().collect(),
+            Item::Attribute(..) => vec![],
+        }
+    };
+    let parent = |item: Item<'a>| -> Option<Item<'a>> {
+        match item {
+            Item::Root => None,
+            Item::Node(node) => Some(node.parent().map_or(Item::Root, Item::Node)),
+            Item::Attribute(node, _) => Some(Item::Node(node)),
+        }
+    };
+    let ancestors = |item: Item<'a>| std::iter::successors(parent(item), |&item| parent(item));
+    let following_siblings = |item: Item<'a>| match item {
+        Item::Node(node) => std::iter::successors(node.next(), |node| node.next()).collect(),
+        _ => vec![],
+    };
+    let preceding_siblings = |item: Item<'a>| match item {
+        Item::Node(node) => std::iter::successors(node.prev(), |node| node.prev()).collect(),
+        _ => vec![],
+    };
+    let with_descendants = |nodes: Vec<Node<'a>>| -> Vec<Node<'a>> {
+        nodes
+            .into_iter()
+            .flat_map(|node| std::iter::once(node).chain(node.descendants()))
+            .collect()
+    };
+
+    let nodes = |nodes: Vec<Node<'a>>| -> Vec<Item<'a>> {
+        nodes
+            .into_iter()
+            .filter(is_visible)
+            .map(Item::Node)
+            .collect()
+    };
+
+    match axis {
+        Axis::Child => nodes(children(item)),
+        Axis::Descendant => nodes(descendants(item)),
+        Axis::DescendantOrSelf => {
+            let mut items = vec![item];
+            items.extend(nodes(descendants(item)));
+            items
+        }
+        Axis::Parent => parent(item).into_iter().collect(),
+        Axis::Ancestor => ancestors(item).collect(),
+        Axis::AncestorOrSelf => std::iter::once(item).chain(ancestors(item)).collect(),
+        Axis::SelfNode => vec![item],
+        Axis::Attribute => match item {
+            Item::Node(node) => (0..node.attrs().count())
+                .map(|index| Item::Attribute(node, index))
+                .collect(),
+            _ => vec![],
+        },
+        Axis::FollowingSibling => nodes(following_siblings(item)),
+        Axis::PrecedingSibling => nodes(preceding_siblings(item)),
+        Axis::Following => {
+            // The following siblings of the item and of each of its ancestors,
+            // with their descendants. An attribute is followed by its
+            // element's descendants.
+            let mut result = match item {
+                Item::Attribute(node, _) => node.descendants().collect(),
+                _ => vec![],
+            };
+            let start = match item {
+                Item::Attribute(node, _) => Item::Node(node),
+                _ => item,
+            };
+            for item in std::iter::once(start).chain(ancestors(start)) {
+                result.extend(with_descendants(following_siblings(item)));
+            }
+            nodes(result)
+        }
+        Axis::Preceding => {
+            let start = match item {
+                Item::Attribute(node, _) => Item::Node(node),
+                _ => item,
+            };
+            let mut result = vec![];
+            for item in std::iter::once(start).chain(ancestors(start)) {
+                let mut siblings = preceding_siblings(item);
+                siblings.reverse();
+                let mut nodes = with_descendants(siblings);
+                nodes.extend(result);
+                result = nodes;
+            }
+            result.reverse();
+            nodes(result)
+        }
+    }
+}
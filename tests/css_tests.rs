@@ -0,0 +1,105 @@
+#![allow(unused_variables)]
+
+pub use select::document::Document;
+pub use select::predicate::css::ParseError;
+pub use select::predicate::*;
+
+use speculate::speculate;
+
+speculate! {
+    describe "css" {
+        before {
+            let document = Document::from("<html><head></head><body>\
+<div class='post' id='first'><a href='http://a.com' data-x='foo-bar'>A</a>\
+<a href='/relative' class='ad'>B</a><!--c--><p>C</p><p></p><span lang='en-US'>D</span></div>\
+<div class='post ad'><a href='https://b.com'>E</a></div>\
+</body></html>");
+
+            let texts = |selector: &str| {
+                document
+                    .find(Css::parse(selector).unwrap())
+                    .map(|node| node.text())
+                    .collect::<Vec<_>>()
+            };
+        }
+
+        test "Css::parse() type, universal, id, and class selectors" {
+            assert_eq!(document.find(Css::parse("a").unwrap()).count(), 3);
+            assert_eq!(document.find(Css::parse("A").unwrap()).count(), 3);
+            assert_eq!(document.find(Css::parse("*").unwrap()).count(), 11);
+            assert_eq!(texts("#first > a"), ["A", "B"]);
+            assert_eq!(texts(".ad"), ["B", "E"]);
+            assert_eq!(texts("div.post.ad a"), ["E"]);
+        }
+
+        test "Css::parse() attribute selectors" {
+            assert_eq!(texts("[href]"), ["A", "B", "E"]);
+            assert_eq!(texts("a[href='/relative']"), ["B"]);
+            assert_eq!(texts("[href^=http]"), ["A", "E"]);
+            assert_eq!(texts("[href$=\".com\"]"), ["A", "E"]);
+            assert_eq!(texts("[href*=b]"), ["E"]);
+            assert_eq!(texts("[data-x~=foo-bar]"), ["A"]);
+            assert_eq!(texts("[lang|=en]"), ["D"]);
+            assert_eq!(texts("[href^=HTTP]"), Vec::<String>::new());
+            assert_eq!(texts("[href^=HTTP i]"), ["A", "E"]);
+        }
+
+        test "Css::parse() combinators" {
+            assert_eq!(texts("body a"), ["A", "B", "E"]);
+            assert_eq!(texts("body > a"), Vec::<String>::new());
+            assert_eq!(texts("a + a"), ["B"]);
+            assert_eq!(texts("a+p"), ["C"]);
+            assert_eq!(texts("a ~ span"), ["D"]);
+            assert_eq!(texts("body div > a ~ p"), ["C", ""]);
+        }
+
+        test "Css::parse() pseudo-classes" {
+            assert_eq!(texts("div.post > a[href^=http]:not(.ad)"), ["A", "E"]);
+            assert_eq!(texts("div.post:not(.ad) > a[href^=http]"), ["A"]);
+            assert_eq!(texts(".post:not(.ad) :first-child"), ["A"]);
+            assert_eq!(texts("#first :last-child"), ["D"]);
+            assert_eq!(texts("#first > :nth-child(2n+1)"), ["A", "C", "D"]);
+            assert_eq!(texts("#first > :nth-child( 2n + 1 )"), ["A", "C", "D"]);
+            assert_eq!(texts("#first > :nth-child(\r2n\r+\x0C1\x0C)"), ["A", "C", "D"]);
+            assert_eq!(texts("#first > :nth-child(even\r)"), ["B", ""]);
+            assert_eq!(texts("#first > :nth-child(2n- 1)"), ["A", "C", "D"]);
+            assert_eq!(texts("#first > :nth-child(2n+ 1)"), ["A", "C", "D"]);
+            assert_eq!(texts("#first > :nth-child(-n+ 2)"), ["A", "B"]);
+            assert_eq!(texts("#first > :nth-child(even)"), ["B", ""]);
+            assert_eq!(texts("#first > :nth-last-child(-n+2)"), ["", "D"]);
+            assert_eq!(texts("#first > p:first-of-type"), ["C"]);
+            assert_eq!(texts("#first > p:nth-of-type(2)"), [""]);
+            assert_eq!(texts("a:only-child"), ["E"]);
+            assert_eq!(texts("span:only-of-type"), ["D"]);
+            assert_eq!(texts("p:empty"), [""]);
+            assert_eq!(document.find(Css::parse(":root").unwrap()).next(), document.nth(0));
+            let template = Document::from("<template><p>t</p><div>u</div></template><p>x</p>");
+            let roots = template.find(Css::parse(":root").unwrap()).map(|node| node.name()).collect::<Vec<_>>();
+            assert_eq!(roots, [Some("html")]);
+            assert_eq!(texts(":is(span, p:empty)"), ["", "D"]);
+        }
+
+        test "Css::parse() selector lists" {
+            assert_eq!(texts("span, a.ad"), ["B", "D"]);
+        }
+
+        test "Css::parse() errors" {
+            let error = |selector: &str| Css::parse(selector).unwrap_err();
+            assert_eq!(error(""), ParseError { position: 0, message: "expected selector" });
+            assert_eq!(error("a >"), ParseError { position: 3, message: "expected selector" });
+            assert_eq!(error("a[href"), ParseError { position: 6, message: "expected attribute operator or ']'" });
+            assert_eq!(error("a:hover"), ParseError { position: 2, message: "unsupported pseudo-class" });
+            assert_eq!(error("a::before"), ParseError { position: 2, message: "pseudo-elements are not supported" });
+            assert_eq!(error("a[x='y]"), ParseError { position: 4, message: "unterminated string" });
+            assert_eq!(error(":nth-child(x)"), ParseError { position: 11, message: "invalid An+B expression" });
+            assert_eq!(error(":nth-child(2n- +1)"), ParseError { position: 11, message: "invalid An+B expression" });
+            assert_eq!(error(":nth-child(2n- )"), ParseError { position: 11, message: "invalid An+B expression" });
+            assert_eq!(error("a)").to_string(), "unexpected character at position 1");
+        }
+
+        test "Selection::filter(Css)" {
+            let all = document.find(Any).into_selection();
+            assert_eq!(all.filter(Css::parse("a.ad").unwrap()).len(), 1);
+        }
+    }
+}
@@ -59,6 +59,65 @@ speculate! {
             assert_eq!(k.parent(), Some(j));
         }
 
+        test "Document::from(&str) with a doctype" {
+            use select::predicate::*;
+
+            let doctype_html = "<!DOCTYPE html PUBLIC \"-//W3C//DTD HTML 4.01//EN\" \
+\"http://www.w3.org/TR/html4/strict.dtd\">";
+            let document = Document::from(&*format!("{}<p>a</p>", doctype_html));
+
+            let doctype = document.nth(0).unwrap();
+            let html = document.nth(1).unwrap();
+            assert_eq!(doctype.as_doctype(), Some("html"));
+            assert_eq!(doctype.data(), &node::Data::Doctype(
+                "html".into(),
+                "-//W3C//DTD HTML 4.01//EN".into(),
+                "http://www.w3.org/TR/html4/strict.dtd".into(),
+            ));
+            assert_eq!(doctype.parent(), None);
+            assert_eq!(doctype.next(), Some(html));
+            assert_eq!(html.prev(), Some(doctype));
+            assert_eq!(doctype.html(), doctype_html);
+            assert_eq!(document.find(Doctype).count(), 1);
+
+            let html = |html: &str| Document::from(html).nth(0).unwrap().html();
+            assert_eq!(html("<!DOCTYPE html>"), "<!DOCTYPE html>");
+            assert_eq!(html("<!doctype html system 'about:legacy-compat'>"),
+                       "<!DOCTYPE html SYSTEM \"about:legacy-compat\">");
+            assert_eq!(html("<!DOCTYPE html PUBLIC 'a\"b'>"), "<!DOCTYPE html PUBLIC 'a\"b'>");
+            assert_eq!(document.find(Doctype.not().and(Element)).count(), 4);
+        }
+
+        test "Document::from(&str) with a template" {
+            use select::predicate::*;
+
+            let document = Document::from("<p>c</p><template id=t><p class=x>a</p><b>b</b></template>");
+
+            let template = document.find(Name("template")).next().unwrap();
+            let contents = template.template_contents().collect::<Vec<_>>();
+            assert_eq!(template.children().count(), 0);
+            assert_eq!(contents.len(), 2);
+            assert_eq!(contents[0].name(), Some("p"));
+            assert_eq!(contents[0].parent(), None);
+            assert_eq!(contents[0].next(), Some(contents[1]));
+            assert_eq!(contents[1].prev(), Some(contents[0]));
+            assert_eq!(template.prev().unwrap().text(), "c");
+            assert_eq!(template.next(), None);
+
+            assert_eq!(document.find(Class("x")).next(), Some(contents[0]));
+            assert_eq!(document.find(Css::parse("template > p").unwrap()).count(), 0);
+            assert_eq!(document.find(Css::parse("template p").unwrap()).count(), 0);
+            assert_eq!(document.find(Name("template").descendant(Name("p"))).count(), 0);
+
+            let html = document.nth(0).unwrap();
+            assert_eq!(html.descendants().filter(|node| node.is(Name("p"))).count(), 1);
+            assert_eq!(html.descendants().include_templates()
+                           .filter(|node| node.is(Name("p"))).count(), 2);
+
+            assert_eq!(template.html(), "<template id=\"t\"><p class=\"x\">a</p><b>b</b></template>");
+            assert_eq!(template.inner_html(), "<p class=\"x\">a</p><b>b</b>");
+        }
+
         test "Docucment::from_read()" {
             use select::predicate::*;
             use std::io::Cursor;
@@ -71,14 +130,400 @@ speculate! {
             assert_eq!(document.unwrap().find(Name("p")).count(), 1);
         }
 
+        test "DocumentBuilder" {
+            use select::document::DocumentBuilder;
+            use select::predicate::*;
+
+            let html = include_str!("fixtures/struct.Vec.html").as_bytes();
+            let mut builder = DocumentBuilder::new();
+            // Chunks of 7 bytes split multi-byte UTF-8 sequences.
+            for chunk in html.chunks(7) {
+                builder.feed(chunk);
+            }
+            let document = builder.finish();
+            assert_eq!(document, Document::from(include_str!("fixtures/struct.Vec.html")));
+
+            let mut builder = DocumentBuilder::new();
+            std::io::copy(&mut "<p>Hello</p>".as_bytes(), &mut builder).unwrap();
+            assert_eq!(builder.finish().find(Name("p")).next().unwrap().text(), "Hello");
+        }
+
+        test "Document::parse_with_diagnostics()" {
+            use select::node::Position;
+
+            let (document, diagnostics) =
+                Document::parse_with_diagnostics("<!DOCTYPE html><p>a\n<div x=1 x=2></p>");
+            assert_eq!(document, Document::from("<!DOCTYPE html><p>a\n<div x=1 x=2></p>"));
+
+            let messages = diagnostics.iter().map(|d| &*d.message).collect::<Vec<_>>();
+            assert_eq!(messages, ["Duplicate attribute", "No <p> tag to close",
+                                  "Unexpected open tag at end of body"]);
+            assert_eq!(diagnostics[0].position,
+                       Some(Position { offset: 20, line: 2, column: 1 }));
+            assert_eq!(diagnostics[1].position,
+                       Some(Position { offset: 33, line: 2, column: 14 }));
+
+            let (_, diagnostics) = Document::parse_with_diagnostics("<!DOCTYPE html><p>a</p>");
+            assert_eq!(diagnostics, []);
+        }
+
+        test "Document::from_fragment()" {
+            use select::predicate::*;
+
+            let document = Document::from_fragment("<td>a</td><td>b</td>", "tr");
+            assert_eq!(document.nodes.len(), 4);
+
+            let a = document.nth(0).unwrap();
+            let b = document.nth(2).unwrap();
+            assert_eq!(a.name(), Some("td"));
+            assert_eq!(a.parent(), None);
+            assert_eq!(a.next(), Some(b));
+            assert_eq!(b.prev(), Some(a));
+            assert_eq!(b.text(), "b");
+            assert_eq!(document.find(Name("td")).count(), 2);
+            assert_eq!(document.find(Name("body")).count(), 0);
+
+            // Without the `tr` context the cells are dropped.
+            let document = Document::from_fragment("<td>a</td><td>b</td>", "body");
+            assert_eq!(document.find(Name("td")).count(), 0);
+            assert_eq!(document.nth(0).unwrap().as_text(), Some("ab"));
+        }
+
         test "Document::find()" {
             use select::predicate::*;
 
             let document = Document::from(include_str!("fixtures/struct.Vec.html"));
-            assert_eq!(document.find(Any).count(), 11446);
+            assert_eq!(document.find(Any).count(), 11447);
             assert_eq!(document.find(Name("div")).count(), 208);
             assert_eq!(document.find(Attr("id", "main")).count(), 1);
             assert_eq!(document.find(Class("struct")).count(), 168);
         }
+
+        test "Document mutation" {
+            use select::predicate::*;
+
+            let mut document = Document::from_fragment("<ul><li>a</li><li>b</li></ul><p>c</p>", "body");
+            let ul = document.find(Name("ul")).next().unwrap().index();
+            let p = document.find(Name("p")).next().unwrap().index();
+            let html = |document: &Document| {
+                document.nth(ul).unwrap().html() + &document.nth(p).unwrap().html()
+            };
+
+            let li = document.create_element("li");
+            let text = document.create_text("z");
+            document.append_child(li, text);
+            assert_eq!(document.find(Name("li")).count(), 2);
+            document.append_child(ul, li);
+            assert_eq!(html(&document), "<ul><li>a</li><li>b</li><li>z</li></ul><p>c</p>");
+
+            let first = document.create_element("li");
+            document.prepend_child(ul, first);
+            document.set_attr(first, "class", "x");
+            let b = document.find(Name("li")).find(|li| li.text() == "b").unwrap().index();
+            let em = document.create_element("em");
+            document.insert_after(b, em);
+            assert_eq!(html(&document),
+                       "<ul><li class=\"x\"></li><li>a</li><li>b</li><em></em><li>z</li></ul><p>c</p>");
+
+            // Inserting a node already in the tree moves it.
+            document.insert_before(first, p);
+            assert_eq!(document.nth(ul).unwrap().html(),
+                       "<ul><p>c</p><li class=\"x\"></li><li>a</li><li>b</li><em></em><li>z</li></ul>");
+            document.insert_after(ul, p);
+
+            document.replace(em, text);
+            document.remove(first);
+            assert!(document.remove_attr(first, "class"));
+            assert!(!document.remove_attr(first, "class"));
+            assert_eq!(html(&document), "<ul><li>a</li><li>b</li>z<li></li></ul><p>c</p>");
+            assert_eq!(document.find(Name("li")).count(), 3);
+            assert_eq!(document.find(Name("em")).count(), 0);
+
+            let ul = document.nth(ul).unwrap();
+            assert_eq!(ul.first_child().unwrap().text(), "a");
+            assert_eq!(ul.last_child().unwrap().prev().unwrap().as_text(), Some("z"));
+            assert_eq!(ul.descendants().count(), 6);
+        }
+
+        test "Document mutation of a template" {
+            use select::predicate::*;
+
+            let mut document = Document::from("<template><b>a</b></template>");
+            let template = document.find(Name("template")).next().unwrap().index();
+            let b = document.find(Name("b")).next().unwrap().index();
+            let i = document.create_element("i");
+            document.insert_before(b, i);
+            assert_eq!(document.nth(template).unwrap().inner_html(), "<i></i><b>a</b>");
+            assert_eq!(document.nth(template).unwrap().children().count(), 0);
+            assert_eq!(document.nth(i).unwrap().parent(), None);
+            assert_eq!(document.find(Css::parse("template > i").unwrap()).count(), 0);
+
+            let mut compacted = document.clone();
+            compacted.compact();
+            let t = compacted.find(Name("template")).next().unwrap();
+            assert_eq!(t.inner_html(), "<i></i><b>a</b>");
+
+            document.detach(i);
+            document.detach(b);
+            assert_eq!(document.nth(template).unwrap().inner_html(), "");
+            assert_eq!(document.find(Name("b")).count(), 0);
+
+            document.append_child(template, b);
+            assert_eq!(document.nth(template).unwrap().children().count(), 1);
+            assert_eq!(document.find(Name("b")).count(), 1);
+
+            let mut document = Document::from("<template><b>a</b></template>");
+            let template = document.find(Name("template")).next().unwrap().index();
+            document.nth_mut(template).unwrap().set_inner_html("<i>b</i>");
+            let node = document.nth(template).unwrap();
+            assert_eq!(node.inner_html(), "<i>b</i>");
+            assert_eq!(node.children().count(), 0);
+            assert_eq!(node.template_contents().count(), 1);
+            assert_eq!(document.find(Name("b")).count(), 0);
+            document.nth_mut(template).unwrap().set_text("c");
+            let node = document.nth(template).unwrap();
+            assert_eq!(node.inner_html(), "c");
+            assert_eq!(node.template_contents().next().unwrap().as_text(), Some("c"));
+            assert_eq!(document.find(Name("i")).count(), 0);
+            document.nth_mut(template).unwrap().set_text("");
+            assert_eq!(document.nth(template).unwrap().html(), "<template></template>");
+            document.nth_mut(template).unwrap().set_inner_html("<i>d</i><u>e</u>");
+            assert_eq!(document.nth(template).unwrap().inner_html(), "<i>d</i><u>e</u>");
+        }
+
+        test "Document::compact()" {
+            use select::document::ParseOptions;
+            use select::predicate::*;
+
+            let html = "<template><b>t</b></template><ul><li>a</li><li>b</li></ul>";
+            let options = ParseOptions { spans: true };
+            let original = Document::parse_with_options(html, options);
+            let mut document = original.clone();
+            let mapping = document.compact();
+            assert_eq!(document, original);
+            assert!(mapping.iter().enumerate().all(|(old, &new)| new == Some(old)));
+
+            let ul = document.find(Name("ul")).next().unwrap().index();
+            let a = document.find(Name("li")).next().unwrap().index();
+            let c = document.create_element("li");
+            document.prepend_child(ul, c);
+            document.nth_mut(c).unwrap().set_text("c");
+            document.remove(a);
+            let len = document.nodes.len();
+
+            let mapping = document.compact();
+            assert_eq!(mapping.len(), len);
+            assert_eq!(mapping[a], None);
+            assert_eq!(mapping[a + 1], None);
+            assert_eq!(document.nodes.len(), len - 2);
+            assert!(document.nodes.iter().enumerate().all(|(i, raw)| raw.index == i));
+            assert!(!document.nodes.iter().any(|raw| document.nth(raw.index).unwrap().is_detached()));
+
+            let texts = document.find(Name("li")).map(|li| li.text()).collect::<Vec<_>>();
+            assert_eq!(texts, ["c", "b"]);
+            let c = document.nth(mapping[c].unwrap()).unwrap();
+            assert_eq!(c.parent().unwrap().index(), mapping[ul].unwrap());
+            assert_eq!(c.span(), None);
+            let b = document.find(Name("li")).nth(1).unwrap();
+            assert_eq!(&html[b.span().unwrap().start.offset..b.span().unwrap().end.offset], "<li>");
+
+            let template = document.find(Name("template")).next().unwrap();
+            assert_eq!(template.inner_html(), "<b>t</b>");
+            assert_eq!(document.find(Any).map(|node| node.index()).collect::<Vec<_>>(),
+                       (0..document.nodes.len()).collect::<Vec<_>>());
+        }
+
+        test "Document::builder()" {
+            use select::predicate::*;
+
+            let document = Document::builder()
+                .comment("generated")
+                .elem("p", |p| {
+                    p.attr("title", "\"quoted\" & <tagged>")
+                        .text("1 < 2")
+                        .elem("br", |br| br)
+                        .elem("a", |a| a.attr("href", "/x").attr("href", "/y").text("link"))
+                })
+                .elem("p", |p| p)
+                .build();
+
+            assert_eq!(document.nodes.len(), 7);
+            assert_eq!(document.find(Name("p")).count(), 2);
+            let p = document.find(Name("p")).next().unwrap();
+            assert_eq!(p.text(), "1 < 2link");
+            assert_eq!(p.attr("title"), Some("\"quoted\" & <tagged>"));
+            assert_eq!(p.html(),
+                       "<p title=\"&quot;quoted&quot; &amp; <tagged>\">1 &lt; 2<br><a href=\"/y\">link</a></p>");
+            assert_eq!(document.nth(0).unwrap().as_comment(), Some("generated"));
+            assert_eq!(document.nth(0).unwrap().next(), Some(p));
+            assert_eq!(p.next().unwrap().html(), "<p></p>");
+
+            let reparsed = Document::from_fragment(&p.html(), "body");
+            assert_eq!(reparsed.nth(0).unwrap().html(), p.html());
+        }
+
+        test "Document::index()" {
+            use select::document::IndexKey;
+            use select::predicate::*;
+
+            let plain = Document::from(include_str!("fixtures/struct.Vec.html"));
+            let indexed = plain.clone();
+            let index = indexed.index();
+            assert_eq!(index.get(IndexKey::Id("main")).len(), 1);
+            assert_eq!(index.get(IndexKey::Name("div")).len(), 208);
+            assert_eq!(index.get(IndexKey::Class("struct")).len(), 168);
+            assert!(index.get(IndexKey::Class("no-such-class")).is_empty());
+            assert_eq!(indexed, plain);
+
+            fn indices<P: Predicate>(document: &Document, predicate: P) -> Vec<usize> {
+                document.find(predicate).map(|node| node.index()).collect()
+            }
+            macro_rules! same {
+                ($predicate:expr) => {
+                    assert_eq!(indices(&indexed, $predicate), indices(&plain, $predicate));
+                };
+            }
+            same!(Name("div"));
+            same!(Name("span").and(Class("struct")));
+            same!(Attr("id", "main"));
+            same!(Class("fqn").descendant(Name("span")));
+            same!(Name("div").child(Class("impl")));
+            same!(Attr("id".to_string(), "main".to_string()));
+            same!(Css::parse("#main .struct").unwrap());
+            same!(Css::parse("div.docblock > p, span.since").unwrap());
+            same!(Name("h2").or(Class("struct")).or(Attr("id", "main")));
+            same!(Name("h2").or(Class("struct")).and(Class("fqn")));
+
+            // Only the candidates from the index are visited.
+            let calls = std::cell::Cell::new(0);
+            let count = |_: &select::node::Node| { calls.set(calls.get() + 1); true };
+            assert_eq!(indexed.find(count.and(Name("div"))).count(), 208);
+            assert_eq!(calls.get(), 208);
+            calls.set(0);
+            assert_eq!(plain.find(count.and(Name("div"))).count(), 208);
+            assert_eq!(calls.get(), 11447);
+            calls.set(0);
+            assert_eq!(indexed.find(count.and(Name("h2").or(Attr("id", "main")))).count(), 4);
+            assert!(calls.get() < 10);
+            calls.set(0);
+            assert_eq!(indexed.find(count.and(Name("h2").or(Any))).count(), 11447);
+            assert_eq!(calls.get(), 11447);
+
+            let main = indexed.find(Attr("id", "main")).into_selection();
+            let plain_main = plain.find(Attr("id", "main")).into_selection();
+            let spans = main.find(Name("span")).iter().map(|node| node.index()).collect::<Vec<_>>();
+            let plain_spans = plain_main.find(Name("span")).iter().map(|node| node.index()).collect::<Vec<_>>();
+            assert_eq!(spans, plain_spans);
+        }
+
+        test "Document::index() is cleared by mutation" {
+            use select::predicate::*;
+
+            let mut document = Document::from("<p class=a>1</p><p>2</p>");
+            assert_eq!(document.index().get(select::document::IndexKey::Class("a")).len(), 1);
+            let p = document.find(Name("p")).nth(1).unwrap().index();
+            document.nth_mut(p).unwrap().add_class("a");
+            assert_eq!(document.index().get(select::document::IndexKey::Class("a")).len(), 2);
+            assert_eq!(document.find(Class("a")).count(), 2);
+            document.nth_mut(p).unwrap().remove_class("a");
+            assert_eq!(document.find(Class("a")).count(), 1);
+            document.set_attr(p, "id", "y");
+            assert_eq!(document.by_id("y").map(|node| node.index()), Some(p));
+
+            let b = document.create_element("b");
+            document.append_child(p, b);
+            document.nth_mut(b).unwrap().set_attr("id", "x");
+            document.index();
+            assert_eq!(document.find(Attr("id", "x")).count(), 1);
+            document.remove(p);
+            assert_eq!(document.find(Attr("id", "x")).count(), 0);
+            assert_eq!(document.find(Name("p")).count(), 1);
+        }
+
+        test "Document::find() with ancestor requirements" {
+            use select::node::Node;
+            use select::predicate::*;
+
+            fn same<P: Predicate>(document: &Document, predicate: P) -> usize {
+                let expected = document
+                    .nodes
+                    .iter()
+                    .map(|raw| document.nth(raw.index).unwrap())
+                    .filter(|node| !node.is_detached() && predicate.matches(node))
+                    .map(|node| node.index())
+                    .collect::<Vec<_>>();
+                let found = document.find(predicate).map(|node| node.index()).collect::<Vec<_>>();
+                assert_eq!(found, expected);
+                found.len()
+            }
+
+            let mut document = Document::from(include_str!("fixtures/struct.Vec.html"));
+            assert_eq!(same(&document, Class("docblock").descendant(Name("code"))), 153);
+            assert_eq!(same(&document, Name("body").descendant(Name("div")).descendant(Name("span"))), 1711);
+            assert_eq!(same(&document, Attr("id", "main").child(Name("h1"))), 1);
+            assert_eq!(same(&document, Name("h1").descendant(Any).or(Name("nav").descendant(Name("a")))), 165);
+            assert_eq!(same(&document, Css::parse("#main .docblock p code, .sidebar a").unwrap()), 153);
+            assert_eq!(same(&document, Class("no-such-class").descendant(Any)), 0);
+
+            // Nodes without a `.fqn` ancestor are rejected before matching.
+            let calls = std::cell::Cell::new(0);
+            let count = |_: &Node| { calls.set(calls.get() + 1); true };
+            assert_eq!(document.find(Class("fqn").descendant(count)).count(), 23);
+            assert!(calls.get() < 30);
+
+            // Also correct when document order no longer matches index order.
+            let main = document.find(Attr("id", "main")).next().unwrap().index();
+            let docblock = document.find(Class("docblock")).nth(3).unwrap().index();
+            let wrapper = document.create_element("section");
+            document.append_child(wrapper, docblock);
+            document.prepend_child(main, wrapper);
+            document.index();
+            assert_eq!(same(&document, Name("section").descendant(Class("docblock")).descendant(Name("p"))), 141);
+            assert_eq!(same(&document, Attr("id", "main").child(Name("section")).descendant(Name("p"))), 2);
+        }
+
+        test "Document::by_id()" {
+            use select::document::ParseOptions;
+            use select::node::Position;
+            use select::predicate::*;
+
+            let html = "<p id=a>1</p>\n<p id=b>2</p><div id=a><b id=a>3</b></div>";
+            let document = Document::from(html);
+            assert_eq!(document.by_id("a").unwrap().text(), "1");
+            assert_eq!(document.by_id("b"), document.find(Id("b")).next());
+            assert_eq!(document.by_id("c"), None);
+            assert_eq!(document.find(Id("a")).count(), 3);
+
+            let messages = document.duplicate_ids().into_iter().map(|d| d.message).collect::<Vec<_>>();
+            assert_eq!(messages, ["Duplicate id \"a\"", "Duplicate id \"a\""]);
+            let document = Document::parse_with_options(html, ParseOptions { spans: true });
+            let positions = document.duplicate_ids().into_iter().map(|d| d.position).collect::<Vec<_>>();
+            assert_eq!(positions, [Some(Position { offset: 27, line: 2, column: 14 }),
+                                   Some(Position { offset: 37, line: 2, column: 24 })]);
+            assert_eq!(Document::from("<p id=a><p id=b>").duplicate_ids(), []);
+
+            let mut document = Document::from(html);
+            let first = document.by_id("a").unwrap().index();
+            document.nth_mut(first).unwrap().remove_attr("id");
+            assert_eq!(document.by_id("a").unwrap().name(), Some("div"));
+            assert_eq!(document.duplicate_ids().len(), 1);
+
+            // The first in document order wins, even after moving nodes.
+            let mut document = Document::parse_with_options("<p id=a>1</p><p id=a>2</p>",
+                                                             ParseOptions { spans: true });
+            let p = document.find(Name("p")).map(|node| node.index()).collect::<Vec<_>>();
+            assert_eq!(document.by_id("a").unwrap().index(), p[0]);
+            assert_eq!(document.duplicate_ids()[0].position.unwrap().offset, 13);
+            document.insert_before(p[0], p[1]);
+            assert_eq!(document.by_id("a").unwrap().text(), "2");
+            let positions = document.duplicate_ids().into_iter().map(|d| d.position.unwrap().offset);
+            assert_eq!(positions.collect::<Vec<_>>(), [0]);
+        }
+
+        test "Document mutation panics on cycles" {
+            let mut document = Document::from_fragment("<div><p></p></div>", "body");
+            let result = std::panic::catch_unwind(move || document.append_child(1, 0));
+            assert!(result.is_err());
+        }
     }
 }
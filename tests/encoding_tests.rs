@@ -0,0 +1,59 @@
+#![cfg(feature = "encoding")]
+
+pub use select::document::Document;
+pub use select::encoding::sniff;
+pub use select::encoding_rs::*;
+
+use speculate::speculate;
+
+speculate! {
+    describe "encoding" {
+        test "sniff() byte order mark" {
+            assert_eq!(sniff(b"\xEF\xBB\xBF<p>", Some("latin1")), UTF_8);
+            assert_eq!(sniff(b"\xFF\xFE<\x00p\x00>\x00", None), UTF_16LE);
+            assert_eq!(sniff(b"\xFE\xFF\x00<\x00p\x00>", None), UTF_16BE);
+        }
+
+        test "sniff() transport hint" {
+            assert_eq!(sniff(b"<meta charset=utf-8>", Some("Shift_JIS")), SHIFT_JIS);
+            assert_eq!(sniff(b"<meta charset=utf-8>", Some("bogus")), UTF_8);
+        }
+
+        test "sniff() <meta> prescan" {
+            assert_eq!(sniff(b"<meta charset=windows-1251>", None), WINDOWS_1251);
+            assert_eq!(sniff(b"<META CHARSET='Shift_JIS'>", None), SHIFT_JIS);
+            assert_eq!(sniff(b"<meta http-equiv=Content-Type \
+                                content='text/html; charset=iso-8859-2'>", None),
+                       ISO_8859_2);
+            // `content` without `http-equiv` is ignored.
+            assert_eq!(sniff(b"<meta content='text/html; charset=iso-8859-2'>\xE9", None),
+                       WINDOWS_1252);
+            // Commented out and attribute values are skipped.
+            assert_eq!(sniff(b"<!-- <meta charset=koi8-r> --><p title='<meta charset=koi8-r>'>",
+                             None),
+                       UTF_8);
+            // UTF-16 in a <meta> means UTF-8.
+            assert_eq!(sniff(b"<meta charset=utf-16le>", None), UTF_8);
+        }
+
+        test "sniff() fallback" {
+            assert_eq!(sniff("<p>caf\u{e9}</p>".as_bytes(), None), UTF_8);
+            assert_eq!(sniff(b"<p>caf\xE9</p>", None), WINDOWS_1252);
+        }
+
+        test "Document::from_read_with_encoding()" {
+            use select::predicate::*;
+            use std::io::Cursor;
+
+            let html = b"<html><head><meta charset=windows-1251></head>\
+<body><p>\xCF\xF0\xE8\xE2\xE5\xF2</p></body></html>";
+            let (document, encoding) =
+                Document::from_read_with_encoding(Cursor::new(&html[..]), None).unwrap();
+            assert_eq!(encoding, WINDOWS_1251);
+            assert_eq!(document.find(Name("p")).next().unwrap().text(), "Привет");
+
+            let document = Document::from_read(Cursor::new(&b"<p>caf\xE9</p>"[..])).unwrap();
+            assert_eq!(document.find(Name("p")).next().unwrap().text(), "café");
+        }
+    }
+}
@@ -171,6 +171,112 @@ speculate! {
             assert_eq!(comment, Some("comment"));
         }
 
+        test "Node::as_doctype() / Node::as_processing_instruction()" {
+            assert_eq!(html.as_doctype(), None);
+            assert_eq!(html.as_processing_instruction(), None);
+
+            let document = Document::from("<!doctype html>");
+            let doctype = document.nth(0).unwrap();
+            assert_eq!(doctype.as_doctype(), Some("html"));
+            assert_eq!(format!("{:?}", doctype),
+                       r#"Doctype { name: "html", public_id: "", system_id: "" }"#);
+
+            let document = Document::from(vec![select::node::Raw {
+                index: 0,
+                parent: None,
+                prev: None,
+                next: None,
+                first_child: None,
+                last_child: None,
+                template_contents: None,
+                data: select::node::Data::ProcessingInstruction("xml-stylesheet".into(),
+                                                                "href=\"a.css\"".into()),
+            }]);
+            let pi = document.nth(0).unwrap();
+            assert_eq!(pi.as_processing_instruction(),
+                       Some(("xml-stylesheet", "href=\"a.css\"")));
+            assert_eq!(pi.html(), "<?xml-stylesheet href=\"a.css\">");
+        }
+
+        test "Node::span()" {
+            use select::document::ParseOptions;
+            use select::node::{Position, Span};
+
+            assert_eq!(html.span(), None);
+
+            let source = "<!DOCTYPE html>\n<div id=a title='x>y'>\n  caf\u{e9} <b>bold</b>\
+<!-- c --><table><tr><td>1</table>";
+            let document = Document::parse_with_options(source,
+                                                        ParseOptions { spans: true });
+            let span = |node: select::node::Node| {
+                node.span().map(|span| &source[span.start.offset..span.end.offset])
+            };
+
+            let nodes = document.nodes.iter()
+                .map(|raw| document.nth(raw.index).unwrap())
+                .collect::<Vec<_>>();
+            let spans = nodes.iter().map(|&node| span(node)).collect::<Vec<_>>();
+            assert_eq!(spans, [
+                Some("<!DOCTYPE html>"),
+                None, // html
+                None, // head
+                None, // body
+                Some("<div id=a title='x>y'>"),
+                Some("\n  caf\u{e9} "),
+                Some("<b>"),
+                Some("bold"),
+                Some("<!-- c -->"),
+                Some("<table>"),
+                None, // tbody
+                Some("<tr>"),
+                Some("<td>"),
+                Some("1"),
+            ]);
+
+            let b = nodes[6];
+            assert_eq!(b.span(), Some(Span {
+                start: Position { offset: 47, line: 3, column: 8 },
+                end: Position { offset: 50, line: 3, column: 11 },
+            }));
+
+            // Text passed on by the parser after a later tag has no span.
+            let spans = |source: &str| {
+                let document = Document::parse_with_options(source, ParseOptions { spans: true });
+                document.nodes.iter()
+                    .map(|raw| document.nth(raw.index).unwrap())
+                    .filter(|node| node.as_text().is_some())
+                    .map(|node| node.span().map(|span| source[span.start.offset..span.end.offset].to_string()))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(spans("<table>x<tr><td>1</table>"), [None, Some("1".into())]);
+            assert_eq!(spans("a<table>x<tr></table>"), [None]);
+            assert_eq!(spans("<table>t<tr></table>"), [None]);
+            assert_eq!(spans("<p>a &amp; b</p><script>a<b>c</script>"),
+                       [Some("a &amp; b".into()), Some("a<b>c".into())]);
+            assert_eq!(spans("<p>a < b</p>"), [Some("a < b".into())]);
+
+            // Columns count characters, and are counted incrementally so long
+            // lines are fast.
+            let spans = |source: &str| {
+                let document = Document::parse_with_options(source, ParseOptions { spans: true });
+                document.nodes.iter()
+                    .filter_map(|raw| document.nth(raw.index).unwrap().span())
+                    .map(|span| (span.start.line, span.start.column, span.end.line, span.end.column))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(spans("<p>\u{e9}\n<b>\u{e9}</b>\u{e9}\n\u{e9}</p>"),
+                       [(1, 1, 1, 4), (1, 4, 2, 1), (2, 1, 2, 4), (2, 4, 2, 5), (2, 9, 3, 2)]);
+            let line = (0..20000).map(|n| format!("<span class=c{}>x</span>", n)).collect::<String>();
+            let document = Document::parse_with_options(&line, ParseOptions { spans: true });
+            let last = document.nth(document.nodes.len() - 1).unwrap().span().unwrap();
+            let offset = line.rfind('x').unwrap();
+            assert_eq!(last.start, Position { offset, line: 1, column: offset + 1 });
+
+            // Spans are not recorded by default.
+            let document = Document::parse_with_options(source, Default::default());
+            assert_eq!(document.nth(4).unwrap().span(), None);
+        }
+
         test "Node::html()" {
             assert_eq!(html.html(), "<html><head></head><body id=\"something\">\
                                      foo<bar>baz<quux class=\"another-thing\">\
@@ -262,6 +368,159 @@ speculate! {
             }
         }
 
+        test "Node element navigation" {
+            let document = Document::from("<ul>\n  <li>a</li>\n  <!--c-->\n  <li>b</li>\n  <li>c</li>\n</ul>");
+            let ul = document.find(select::predicate::Name("ul")).next().unwrap();
+            let lis = ul.element_children().collect::<Vec<_>>();
+            assert_eq!(lis.iter().map(|li| li.text()).collect::<Vec<_>>(), ["a", "b", "c"]);
+            assert_eq!(ul.children().count(), 9);
+
+            assert_eq!(ul.first_element_child(), Some(lis[0]));
+            assert_eq!(ul.last_element_child(), Some(lis[2]));
+            assert_eq!(ul.first_child().unwrap().as_text(), Some("\n  "));
+            assert_eq!(lis[0].next_element(), Some(lis[1]));
+            assert_eq!(lis[1].prev_element(), Some(lis[0]));
+            assert_eq!(lis[2].next_element(), None);
+            assert_eq!(lis[0].prev_element(), None);
+            assert_eq!(lis.iter().map(|li| li.element_index()).collect::<Vec<_>>(),
+                       [Some(0), Some(1), Some(2)]);
+            assert_eq!(ul.first_child().unwrap().element_index(), None);
+            assert_eq!(lis[0].first_element_child(), None);
+            assert_eq!(lis[0].last_element_child(), None);
+
+            assert_eq!(lis[1].following_siblings().count(), 3);
+            assert_eq!(lis[1].preceding_siblings().count(), 5);
+            assert_eq!(lis[1].preceding_siblings().nth(1).unwrap().as_comment(), Some("c"));
+            assert_eq!(lis[1].following_siblings().into_selection().len(), 3);
+        }
+
+        test "Node ancestors" {
+            use select::predicate::*;
+
+            let document = Document::from("<table><tr class=row><td><b>1</b></td></tr></table>");
+            let b = document.find(Name("b")).next().unwrap();
+            let names = |nodes: select::node::Ancestors| nodes.filter_map(|node| node.name().map(String::from)).collect::<Vec<_>>();
+            assert_eq!(names(b.ancestors()), ["td", "tr", "tbody", "table", "body", "html"]);
+            assert_eq!(names(b.ancestors_or_self())[..2], ["b", "td"]);
+            assert_eq!(b.ancestors().into_selection().len(), 6);
+
+            let tr = b.closest(Name("tr")).unwrap();
+            assert_eq!(tr.attr("class"), Some("row"));
+            assert_eq!(b.closest(Name("b")), Some(b));
+            assert_eq!(b.closest(Name("ul")), None);
+
+            assert!(tr.is_ancestor_of(&b));
+            assert!(!b.is_ancestor_of(&tr));
+            assert!(!b.is_ancestor_of(&b));
+            let other = document.clone();
+            assert!(!tr.is_ancestor_of(&other.find(Name("b")).next().unwrap()));
+
+            assert_eq!(document.nth(0).unwrap().depth(), 0);
+            assert_eq!(b.depth(), 6);
+        }
+
+        test "Descendants (double-ended)" {
+            let document = Document::from(include_str!("fixtures/struct.Vec.html"));
+            for i in (0..document.nodes.len()).step_by(7) {
+                let node = document.nth(i).unwrap();
+                let mut forward = node.descendants().map(|node| node.index()).collect::<Vec<_>>();
+                forward.reverse();
+                let backward = node.descendants().rev().map(|node| node.index()).collect::<Vec<_>>();
+                assert_eq!(forward, backward);
+            }
+
+            let document = Document::from("<p>a<b>b<i>c</i></b>d</p><template><u>e</u>f</template>");
+            let p = document.find(select::predicate::Name("p")).next().unwrap();
+            let mut descendants = p.descendants();
+            assert_eq!(descendants.next().unwrap().as_text(), Some("a"));
+            assert_eq!(descendants.next_back().unwrap().as_text(), Some("d"));
+            assert_eq!(descendants.next_back().unwrap().as_text(), Some("c"));
+            assert_eq!(descendants.next().unwrap().name(), Some("b"));
+            assert_eq!(descendants.next().unwrap().as_text(), Some("b"));
+            assert_eq!(descendants.next_back().unwrap().name(), Some("i"));
+            assert_eq!(descendants.next(), None);
+            assert_eq!(descendants.next_back(), None);
+
+            let template = document.find(select::predicate::Name("template")).next().unwrap();
+            assert_eq!(template.descendants().rev().count(), 0);
+            let texts = template.descendants().include_templates().rev()
+                .map(|node| node.text()).collect::<Vec<_>>();
+            assert_eq!(texts, ["f", "e", "e"]);
+        }
+
+        test "Node::descendants_bfs()" {
+            let document = Document::from("<div><p>a<b>b</b></p><p>c</p></div>");
+            let div = document.find(select::predicate::Name("div")).next().unwrap();
+            let nodes = div.descendants_bfs().map(|node| {
+                node.name().map_or_else(|| node.text(), String::from)
+            }).collect::<Vec<_>>();
+            assert_eq!(nodes, ["p", "p", "a", "b", "c", "b"]);
+            assert_eq!(div.descendants_bfs().count(), div.descendants().count());
+        }
+
+        test "Node::traverse()" {
+            use select::node::Edge;
+
+            let document = Document::from("<div><p>a<br></p>b</div>");
+            let div = document.find(select::predicate::Name("div")).next().unwrap();
+            let mut output = String::new();
+            for edge in div.traverse() {
+                match edge {
+                    Edge::Open(node) => match node.name() {
+                        Some(name) => output += &format!("<{}>", name),
+                        None => output += &node.text(),
+                    },
+                    Edge::Close(node) => if let Some(name) = node.name() {
+                        output += &format!("</{}>", name);
+                    },
+                }
+            }
+            assert_eq!(output, "<div><p>a<br></br></p>b</div>");
+            assert_eq!(div.traverse().count(), 2 * (1 + div.descendants().count()));
+            let text = div.find(select::predicate::Text).next().unwrap();
+            assert_eq!(text.traverse().collect::<Vec<_>>(), [Edge::Open(text), Edge::Close(text)]);
+        }
+
+        test "NodeMut" {
+            use select::predicate::*;
+
+            let mut document = Document::from("<div><a href='/x' class='a tracked'>x</a><p>y</p></div>");
+            let a = document.find(Name("a")).next().unwrap().index();
+            let p = document.find(Name("p")).next().unwrap().index();
+
+            {
+                let mut a = document.nth_mut(a).unwrap();
+                a.set_attr("href", "https://example.com/x");
+                a.set_attr("rel", "nofollow");
+                a.remove_class("tracked");
+                assert!(!a.remove_class("tracked"));
+                a.add_class("b");
+                a.add_class("a");
+                assert!(a.remove_attr("rel"));
+                a.set_text("<link>");
+                a.rename("span");
+                assert_eq!(a.as_node().html(),
+                           "<span href=\"https://example.com/x\" class=\"a b\">&lt;link&gt;</span>");
+            }
+
+            let mut p = document.nth_mut(p).unwrap();
+            p.set_inner_html("<b>1</b><td>dropped</td>2");
+            assert_eq!(p.as_node().inner_html(), "<b>1</b>dropped2");
+            p.set_text("");
+            assert_eq!(p.as_node().children().count(), 0);
+
+            let mut document = Document::from("<table></table>");
+            let table = document.find(Name("table")).next().unwrap().index();
+            document.nth_mut(table).unwrap().set_inner_html("<tr><td>a</td></tr>");
+            assert_eq!(document.find(Name("td")).count(), 1);
+            assert_eq!(document.find(Name("tbody")).count(), 1);
+            assert_eq!(document.nth(table).unwrap().find(Name("td")).next().unwrap().text(), "a");
+
+            let mut document = Document::from("<!--a-->");
+            document.nth_mut(0).unwrap().set_text("b");
+            assert_eq!(document.nth(0).unwrap().as_comment(), Some("b"));
+        }
+
         // https://github.com/utkarshkukreti/select.rs/pull/38
         test "issue #38" {
             {
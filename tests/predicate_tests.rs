@@ -58,6 +58,14 @@ speculate! {
             assert!(!Class("bar").matches(&article));
         }
 
+        test "Id()" {
+            assert!(!Id("post-0").matches(&html));
+            assert!(Id("post-0").matches(&article));
+            assert!(Id(String::from("post-0")).matches(&article));
+            assert!(!Id("post").matches(&article));
+            assert!(!Id("post-0").matches(&foo));
+        }
+
         test "Not()" {
             assert!(!Not(Name("html")).matches(&html));
             assert!(Not(Name("html")).matches(&head));
@@ -72,6 +80,36 @@ speculate! {
             assert!(Attr("id", ()).matches(&article));
         }
 
+        test "Attr() with AttrValue conditions" {
+            assert!(Attr("id", StartsWith("post-")).matches(&article));
+            assert!(!Attr("id", StartsWith("-0")).matches(&article));
+            assert!(!Attr("id", StartsWith("")).matches(&article));
+            assert!(!Attr("id", StartsWith("post-0-1")).matches(&article));
+            assert!(Attr("id", EndsWith("-0")).matches(&article));
+            assert!(!Attr("id", EndsWith("post")).matches(&article));
+            assert!(Attr("class", Contains("y-fo")).matches(&article));
+            assert!(!Attr("class", Contains("")).matches(&article));
+            assert!(Attr("class", Token("category-foo")).matches(&article));
+            assert!(!Attr("class", Token("category")).matches(&article));
+            assert!(!Attr("class", Token("post category-foo")).matches(&article));
+            assert!(Attr("id", DashPrefix("post")).matches(&article));
+            assert!(Attr("id", DashPrefix("post-0")).matches(&article));
+            assert!(!Attr("id", DashPrefix("pos")).matches(&article));
+            assert!(!Attr("href", Contains("post")).matches(&article));
+            assert!(Attr(String::from("id"), StartsWith(String::from("post"))).matches(&article));
+
+            assert!(!Attr("id", "POST-0").matches(&article));
+            assert!(Attr("id", IgnoreCase("POST-0")).matches(&article));
+            assert!(Attr("id", IgnoreCase(StartsWith("Post"))).matches(&article));
+            assert!(Attr("class", IgnoreCase(Token("TAG-BAR"))).matches(&article));
+            assert!(!Attr("class", IgnoreCase(Token("TAG"))).matches(&article));
+
+            assert_eq!(Attr("id", "post-0").hint().id, Some("post-0"));
+            assert_eq!(Attr("id", StartsWith("post")).hint().id, None);
+            assert_eq!(Attr("id", IgnoreCase("post-0")).hint().id, None);
+            assert_eq!(Css::parse("[id=post-0]").unwrap().hint().id, Some("post-0"));
+        }
+
         test "Fn(&Node) -> bool" {
             let f = |node: &node::Node| node.name() == Some("html");
             assert!(f.matches(&html));
@@ -137,7 +175,7 @@ speculate! {
             let check = |parent: &str, child: &str, matching: Option<usize>| {
                 let selector = Descendant(Class(parent), Class(child));
                 for node in &[a, b, c, d] {
-                    let expected = matching.map_or(false, |index| node.index() == index);
+                    let expected = matching == Some(node.index());
                     assert_eq!(selector.matches(node), expected);
                 }
             };
@@ -171,5 +209,47 @@ speculate! {
             assert!(not_html.matches(&head));
             assert!(not_html.matches(&article));
         }
+
+        test "Predicate::hint()" {
+            let hint = |name, id, classes: &[&'static str], attrs: &[&'static str]| PredicateHint {
+                name,
+                id,
+                classes: classes.to_vec(),
+                attrs: attrs.to_vec(),
+                ..Default::default()
+            };
+            let div = hint(Some("div"), None, &[], &[]);
+            let a = hint(None, None, &["a"], &[]);
+
+            assert!(Any.hint().is_empty());
+            assert!(Not(Name("div")).hint().is_empty());
+            assert_eq!(Name("div").hint(), div);
+            assert_eq!(Name("div".to_string()).hint(), div);
+            assert_eq!(Class("a").hint(), a);
+            assert_eq!(Attr("id", "post-0").hint(), hint(None, Some("post-0"), &[], &["id"]));
+            assert_eq!(Attr("href", ()).hint(), hint(None, None, &[], &["href"]));
+            assert_eq!(Name("div").and(Class("a")).and(Class("b")).hint(),
+                       hint(Some("div"), None, &["a", "b"], &[]));
+
+            let or = Name("div").or(Class("a").or(Name("p")));
+            assert_eq!(or.hint(), PredicateHint {
+                any_of: vec![div.clone(), a.clone(), hint(Some("p"), None, &[], &[])],
+                ..Default::default()
+            });
+            assert!(Name("div").or(Any).hint().is_empty());
+
+            let descendant = Class("a").descendant(Name("div").child(Class("d")));
+            assert_eq!(descendant.hint(), PredicateHint {
+                ancestors: vec![div.clone(), a.clone()],
+                ..hint(None, None, &["d"], &[])
+            });
+
+            let css = Css::parse("div.a > p, #x").unwrap();
+            let hints = css.hint().any_of;
+            assert_eq!(hints.len(), 2);
+            assert_eq!(hints[0].name, Some("p"));
+            assert_eq!(hints[0].ancestors, [hint(Some("div"), None, &["a"], &[])]);
+            assert_eq!(hints[1].id, Some("x"));
+        }
     }
 }
@@ -27,7 +27,7 @@ speculate! {
             let document = Document::from(include_str!("fixtures/struct.Vec.html"));
             let all = document.find(Any).into_selection();
 
-            assert_eq!(all.filter(Any).len(), 11446);
+            assert_eq!(all.filter(Any).len(), 11447);
 
             let divs = all.filter(Name("div"));
             assert_eq!(divs.len(), 208);
@@ -145,6 +145,81 @@ speculate! {
             check(&document, Name("span"));
         }
 
+        test "Selection set operations" {
+            let document = Document::from("<p>0</p><p>1</p><p>2</p><p>3</p>");
+            let select = |indices: &[usize]| Selection::new(&document, indices.iter().cloned().collect());
+            let indices = |selection: Selection| selection.iter().map(|node| node.index()).collect::<Vec<_>>();
+            let a = select(&[3, 5, 7]);
+            let b = select(&[5, 7, 9]);
+
+            assert_eq!(indices(a.union(&b)), [3, 5, 7, 9]);
+            assert_eq!(indices(a.intersection(&b)), [5, 7]);
+            assert_eq!(indices(a.difference(&b)), [3]);
+            assert_eq!(indices(a.symmetric_difference(&b)), [3, 9]);
+            assert_eq!(&a | &b, a.union(&b));
+            assert_eq!(&a & &b, a.intersection(&b));
+            assert_eq!(&a - &b, a.difference(&b));
+            assert_eq!(a.clone() ^ b.clone(), a.symmetric_difference(&b));
+
+            assert!(a.contains(&document.nth(3).unwrap()));
+            assert!(!a.contains(&document.nth(9).unwrap()));
+            let other = document.clone();
+            assert!(!a.contains(&other.nth(3).unwrap()));
+        }
+
+        test "Selection set operations panic on different documents" {
+            let document = Document::from("<p>0</p>");
+            let other = document.clone();
+            let a = Selection::new(&document, [0].iter().cloned().collect());
+            let b = Selection::new(&other, [0].iter().cloned().collect());
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| a.union(&b)));
+            assert!(result.is_err());
+        }
+
+        test "Selection traversal" {
+            use select::predicate::*;
+
+            let document = Document::from("<div id=outer><div id=inner><ul>\
+<li id=a>a</li> <li id=b class=x>b</li> <li id=c>c</li> <li id=d class=x>d</li>\
+</ul></div></div><p id=e>e</p>");
+            let ids = |selection: Selection| {
+                selection.iter().filter_map(|node| node.attr("id").map(String::from)).collect::<Vec<_>>()
+            };
+            let b = document.find(Attr("id", "b")).into_selection();
+            let lis = document.find(Name("li")).into_selection();
+
+            assert_eq!(ids(b.closest(Name("div"))), ["inner"]);
+            assert_eq!(ids(b.closest(Name("li"))), ["b"]);
+            assert_eq!(ids(b.siblings()), ["a", "c", "d"]);
+            assert_eq!(b.siblings().len(), 6);
+            assert_eq!(ids(b.element_siblings()), ["a", "c", "d"]);
+            assert_eq!(b.element_siblings().len(), 3);
+            assert_eq!(ids(b.next_all().filter(Element)), ["c", "d"]);
+            assert_eq!(ids(b.prev_all()), ["a"]);
+            assert_eq!(ids(b.next_until(Class("x"))), ["c"]);
+            let d = document.find(Attr("id", "d")).into_selection();
+            assert_eq!(ids(d.prev_until(Attr("id", "a"))), ["b", "c"]);
+            assert_eq!(ids(b.parents_until(Attr("id", "outer"))), ["inner"]);
+            assert_eq!(ids(b.next_element()), ["c"]);
+            assert_eq!(ids(b.prev_element()), ["a"]);
+            assert_eq!(b.next().iter().next().unwrap().as_text(), Some(" "));
+
+            let divs = document.find(Name("div")).into_selection();
+            assert_eq!(ids(divs.has(Class("x"))), ["outer", "inner"]);
+            assert_eq!(ids(divs.element_children()), ["inner"]);
+            assert_eq!(ids(lis.not(Class("x"))), ["a", "c"]);
+            assert_eq!(ids(lis.get(2)), ["c"]);
+            assert!(lis.get(4).is_empty());
+            assert_eq!(ids(lis.slice(1..3)), ["b", "c"]);
+            assert_eq!(ids(lis.slice(2..)), ["c", "d"]);
+            assert_eq!(ids(lis.slice(..=0)), ["a"]);
+            assert!(lis.get(usize::MAX).is_empty());
+            assert_eq!(ids(lis.slice(2..=usize::MAX)), ["c", "d"]);
+            assert!(lis.slice((std::ops::Bound::Excluded(usize::MAX), std::ops::Bound::Unbounded)).is_empty());
+            let p = document.find(Name("p")).into_selection();
+            assert_eq!(ids(lis.get(0).add(&p)), ["a", "e"]);
+        }
+
         test "Iter (lifetimes)" {
             let document = Document::from("<html><head></head><body>\
 <article id='post-0' class='post category-foo tag-bar'></article>\
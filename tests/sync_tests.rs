@@ -0,0 +1,36 @@
+pub use select::document::Document;
+pub use select::node::Node;
+pub use select::predicate::*;
+pub use select::selection::Selection;
+
+use speculate::speculate;
+
+speculate! {
+    describe "sync" {
+        test "Document, Node and Selection are Send + Sync" {
+            fn check<T: Send + Sync>() {}
+            check::<Document>();
+            check::<Node>();
+            check::<Selection>();
+        }
+
+        test "querying a Document from multiple threads" {
+            use std::sync::Arc;
+
+            let document = Arc::new(Document::from(include_str!("fixtures/struct.Vec.html")));
+            let handles = (0..4).map(|_| {
+                let document = Arc::clone(&document);
+                std::thread::spawn(move || document.find(Class("struct")).count())
+            }).collect::<Vec<_>>();
+            for handle in handles {
+                assert_eq!(handle.join().unwrap(), 168);
+            }
+
+            let divs = document.find(Name("div")).into_selection();
+            std::thread::scope(|scope| {
+                scope.spawn(|| assert_eq!(divs.len(), 208));
+                scope.spawn(|| assert_eq!(divs.first().unwrap().name(), Some("div")));
+            });
+        }
+    }
+}
@@ -0,0 +1,129 @@
+#![allow(unused_variables)]
+
+pub use select::document::Document;
+pub use select::predicate::*;
+pub use select::xpath::{EvalError, ParseError, Value, XPath};
+
+use speculate::speculate;
+
+speculate! {
+    describe "xpath" {
+        before {
+            let document = Document::from("<html><head></head><body>\
+<div id='a' class='post'><p>One</p><p>  Two\n  words </p><!--c--><span lang='en-US'>x</span></div>\
+<div id='b'><p>Three</p><a href='/x'>4</a><a href='/y'>5</a></div>\
+</body></html>");
+
+            let eval = |expr: &str| XPath::parse(expr).unwrap().evaluate_document(&document).unwrap();
+            let texts = |expr: &str| match eval(expr) {
+                Value::Nodes(selection) => selection.iter().map(|node| node.text()).collect::<Vec<_>>(),
+                value => panic!("expected a node-set, got {:?}", value),
+            };
+        }
+
+        test "XPath location paths" {
+            assert_eq!(texts("//p"), ["One", "  Two\n  words ", "Three"]);
+            assert_eq!(texts("/html/body/div[2]/p"), ["Three"]);
+            assert_eq!(texts("//div[@id='a']/p[last()]"), ["  Two\n  words "]);
+            assert_eq!(texts("//p[2]"), ["  Two\n  words "]);
+            assert_eq!(texts("(//p)[3]"), ["Three"]);
+            assert_eq!(texts("//a[. = '5']/preceding-sibling::*[1]"), ["4"]);
+            assert_eq!(texts("//a/ancestor::div/p[1]"), ["Three"]);
+            assert_eq!(texts("//span/following::p"), ["Three"]);
+            assert_eq!(texts("//a[1]/preceding::p"), ["One", "  Two\n  words ", "Three"]);
+            assert_eq!(texts("//div[p = 'Three'] | //span"), ["x", "Three45"]);
+            assert_eq!(texts("//div[1]/text()"), Vec::<String>::new());
+            assert_eq!(texts("//p/text()"), ["One", "  Two\n  words ", "Three"]);
+            assert_eq!(eval("string(//div[1]/comment())"), Value::String("c".into()));
+            assert_eq!(texts("//*[lang('en')]"), ["x"]);
+            assert_eq!(texts("/"), Vec::<String>::new());
+        }
+
+        test "XPath attributes" {
+            match eval("//a/@href") {
+                Value::Attributes(attributes) => {
+                    let values = attributes.iter().map(|attr| attr.value).collect::<Vec<_>>();
+                    assert_eq!(values, ["/x", "/y"]);
+                    assert_eq!(attributes[0].name, "href");
+                    assert_eq!(attributes[0].element.text(), "4");
+                }
+                value => panic!("expected attributes, got {:?}", value),
+            }
+            assert_eq!(texts("//*[@class]"), ["One  Two\n  words x"]);
+            assert_eq!(eval("string(//div[2]/a[2]/@href)"), Value::String("/y".into()));
+        }
+
+        test "XPath functions and scalars" {
+            assert_eq!(eval("count(//p)"), Value::Number(3.0));
+            assert_eq!(eval("normalize-space(//p[2])"), Value::String("Two words".into()));
+            assert_eq!(eval("contains(//div[2], 'Three')"), Value::Boolean(true));
+            assert_eq!(eval("count(//p[position() > 1])"), Value::Number(1.0));
+            assert_eq!(eval("count((//p)[position() > 1])"), Value::Number(2.0));
+            assert_eq!(eval("sum(//a) div 2"), Value::Number(4.5));
+            assert_eq!(eval("concat('a', 1 + 1, true())"), Value::String("a2true".into()));
+            assert_eq!(eval("substring('12345', 1.5, 2.6)"), Value::String("234".into()));
+            assert_eq!(eval("substring-before('a=b', '=')"), Value::String("a".into()));
+            assert_eq!(eval("translate('bar', 'abc', 'AB')"), Value::String("BAr".into()));
+            assert_eq!(eval("string-length('héllo')"), Value::Number(5.0));
+            assert_eq!(eval("round(-2.5) = -2 and floor(1.5) = 1 and ceiling(1.5) = 2"), Value::Boolean(true));
+            assert_eq!(eval("7 mod 3 - -1"), Value::Number(2.0));
+            assert_eq!(eval("//a = 5"), Value::Boolean(true));
+            assert_eq!(eval("//a != 4"), Value::Boolean(true));
+            assert_eq!(eval("//a > 5"), Value::Boolean(false));
+            assert_eq!(eval("//missing = false()"), Value::Boolean(true));
+            assert_eq!(eval("//missing != true()"), Value::Boolean(true));
+            assert_eq!(eval("true() = //missing"), Value::Boolean(false));
+            assert_eq!(eval("//a = true()"), Value::Boolean(true));
+            assert_eq!(eval("//a != false()"), Value::Boolean(true));
+            assert_eq!(eval("false() = //a"), Value::Boolean(false));
+            assert_eq!(eval("//a > false()"), Value::Boolean(true));
+            assert_eq!(eval("name(//div/*[1])"), Value::String("p".into()));
+            assert!(eval("number('x')").number().is_nan());
+            assert_eq!(eval("string(1 div 0)"), Value::String("Infinity".into()));
+        }
+
+        test "XPath::evaluate() relative to a node" {
+            let div = document.nth(0).unwrap().find(Name("div")).nth(1).unwrap();
+            let xpath = XPath::parse("a[2]").unwrap();
+            assert_eq!(xpath.evaluate(&div).unwrap().string(), "5");
+            let xpath = XPath::parse("count(../div)").unwrap();
+            assert_eq!(xpath.evaluate(&div).unwrap(), Value::Number(2.0));
+            let xpath = XPath::parse("count(//p)").unwrap();
+            assert_eq!(xpath.evaluate(&div).unwrap(), Value::Number(3.0));
+        }
+
+        test "XPath after mutation" {
+            let mut document = Document::from("<div><p a='1'>1</p><p a='2'>2</p></div>");
+            let p = document.find(Name("p")).map(|node| node.index()).collect::<Vec<_>>();
+            document.insert_before(p[0], p[1]);
+
+            let eval = |expr: &str| XPath::parse(expr).unwrap().evaluate_document(&document).unwrap();
+            assert_eq!(eval("string(//p)"), Value::String("2".into()));
+            assert_eq!(eval("string((//p)[1])"), Value::String("2".into()));
+            assert_eq!(eval("string((//p | //div)[2])"), Value::String("2".into()));
+            assert_eq!(eval("string(//p/@a)"), Value::String("2".into()));
+            match eval("//@a") {
+                Value::Attributes(attributes) => {
+                    let values = attributes.iter().map(|attr| attr.value).collect::<Vec<_>>();
+                    assert_eq!(values, ["2", "1"]);
+                }
+                value => panic!("expected attributes, got {:?}", value),
+            }
+        }
+
+        test "XPath errors" {
+            let error = |expr: &str| XPath::parse(expr).unwrap_err();
+            assert_eq!(error(""), ParseError { position: 0, message: "expected node test" });
+            assert_eq!(error("//p["), ParseError { position: 4, message: "expected node test" });
+            assert_eq!(error("//p[1"), ParseError { position: 5, message: "expected ']'" });
+            assert_eq!(error("foo()"), ParseError { position: 0, message: "unknown function" });
+            assert_eq!(error("count()"), ParseError { position: 0, message: "wrong number of arguments" });
+            assert_eq!(error("$x"), ParseError { position: 0, message: "variables are not supported" });
+            assert_eq!(error("'a"), ParseError { position: 0, message: "unterminated string literal" });
+            assert_eq!(error("p p"), ParseError { position: 2, message: "expected operator" });
+
+            let xpath = XPath::parse("count('a')").unwrap();
+            assert_eq!(xpath.evaluate_document(&document), Err(EvalError { message: "expected a node-set" }));
+        }
+    }
+}